@@ -5,11 +5,107 @@
 #![no_std]
 #![no_main]
 
+/// Set once a panic is being reported, so that a panic in the report itself just halts.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
 #[panic_handler]
-fn panic(_info:&core::panic::PanicInfo) -> ! {
+fn panic(info:&core::panic::PanicInfo) -> ! {
+    interrupts::disable();
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        let _ = report(&mut VgaScreen::panic_screen(), info);
+        let _ = report(&mut Com1, info);
+    }
     loop{hlt();}
 }
 
+fn report(out: &mut impl Write, info: &core::panic::PanicInfo) -> fmt::Result {
+    writeln!(out, "KERNEL PANIC")?;
+    writeln!(out)?;
+    writeln!(out, "{}", info.message())?;
+    if let Some(location) = info.location() {
+        writeln!(out, "at {}", location)?;
+    }
+    Ok(())
+}
+
+const VGA_BUFFER: *mut u8 = 0xb8000 as *mut u8;
+const VGA_COLUMNS: usize = 80;
+const VGA_ROWS: usize = 25;
+/// White text on a red background.
+const PANIC_ATTRIBUTE: u8 = 0x4f;
+
+/// Writes text to the VGA text buffer, dropping whatever does not fit on the screen.
+struct VgaScreen {
+    column: usize,
+    row: usize,
+}
+
+impl VgaScreen {
+    /// Fills the screen with the panic colors and starts writing at the top left.
+    fn panic_screen() -> Self {
+        for cell in 0..VGA_COLUMNS * VGA_ROWS {
+            put_cell(cell, b' ');
+        }
+        Self { column: 0, row: 0 }
+    }
+}
+
+fn put_cell(cell: usize, byte: u8) {
+    unsafe {
+        VGA_BUFFER.add(cell * 2).write_volatile(byte);
+        VGA_BUFFER.add(cell * 2 + 1).write_volatile(PANIC_ATTRIBUTE);
+    }
+}
+
+impl Write for VgaScreen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' || self.column == VGA_COLUMNS {
+                self.column = 0;
+                self.row += 1;
+            }
+            if byte == b'\n' || self.row == VGA_ROWS {
+                continue;
+            }
+            // the VGA font is code page 437, which matches ASCII only
+            let byte = if byte.is_ascii_graphic() || byte == b' ' { byte } else { 0xfe };
+            put_cell(self.row * VGA_COLUMNS + self.column, byte);
+            self.column += 1;
+        }
+        Ok(())
+    }
+}
+
+/// The first serial port, which QEMU's `-serial stdio` shows. The firmware leaves it set up
+/// well enough to send on.
+struct Com1;
+
+const COM1_DATA: u16 = 0x3f8;
+const COM1_LINE_STATUS: u16 = 0x3fd;
+const TRANSMIT_EMPTY: u8 = 1 << 5;
+
+impl Write for Com1 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                send(b'\r');
+            }
+            send(byte);
+        }
+        Ok(())
+    }
+}
+
+fn send(byte: u8) {
+    unsafe {
+        // an absent port reads as all ones, so this does not spin forever
+        while Port::<u8>::new(COM1_LINE_STATUS).read() & TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        Port::new(COM1_DATA).write(byte);
+    }
+}
+
 static HELLO: &[u8] = b"Hello World! This is just a quick illustration";
 
 #[no_mangle]
@@ -27,4 +123,7 @@ pub extern "C" fn _start() -> !{
     loop{hlt();}
 }
 
-use x86_64::instructions::{hlt};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use x86_64::instructions::{hlt, interrupts, port::Port};
@@ -1,6 +1,16 @@
 // build.rs
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+// Layout of the kernel's symbol table, see the kernel's backtrace module. All numbers are
+// little-endian.
+const SYMBOL_TABLE_SECTION: &str = ".kernel_symbols";
+const SYMBOL_TABLE_EMPTY_MAGIC: &[u8] = b"KSYMNONE";
+const SYMBOL_TABLE_MAGIC: &[u8] = b"KSYMTAB1";
+const SYMBOL_TABLE_HEADER_SIZE: usize = 24;
+const SYMBOL_TABLE_ENTRY_SIZE: usize = 20;
 
 fn main() {
     // set by cargo, build scripts should use this directory for output files
@@ -16,16 +26,113 @@ fn main() {
     // https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies
 
     let kernel = PathBuf::from(std::env::var_os("CARGO_BIN_FILE_KERNEL_WITH_BOOTLOADER").unwrap());
+    let kernel = embed_symbol_table(&kernel, &out_dir);
     
+    // a PC Screen Font (.psf) for the kernel console can be passed in KERNEL_FONT, or a BMP,
+    // TGA or QOI image to show at boot instead of the built-in logo in KERNEL_SPLASH. Either is
+    // loaded as the ramdisk, which the kernel checks for a font first
+    println!("cargo:rerun-if-env-changed=KERNEL_FONT");
+    println!("cargo:rerun-if-env-changed=KERNEL_SPLASH");
+    let font = std::env::var_os("KERNEL_FONT").map(PathBuf::from);
+    let splash = std::env::var_os("KERNEL_SPLASH").map(PathBuf::from);
+    if font.is_some() && splash.is_some() {
+        println!("cargo:warning=only one ramdisk fits, ignoring KERNEL_SPLASH for KERNEL_FONT");
+    }
+    let ramdisk = font.or(splash);
+
     // create an UEFI disk image (optional)
     let uefi_path = out_dir.join("uefi.img");
-    bootloader::UefiBoot::new(&kernel).create_disk_image(&uefi_path).unwrap();
+    let mut uefi = bootloader::UefiBoot::new(&kernel);
+    if let Some(ramdisk) = &ramdisk {
+        uefi.set_ramdisk(ramdisk);
+    }
+    uefi.create_disk_image(&uefi_path).unwrap();
 
     // create a BIOS disk image
     let bios_path = out_dir.join("bios.img");
-    bootloader::BiosBoot::new(&kernel).create_disk_image(&bios_path).unwrap();
+    let mut bios = bootloader::BiosBoot::new(&kernel);
+    if let Some(ramdisk) = &ramdisk {
+        bios.set_ramdisk(ramdisk);
+    }
+    bios.create_disk_image(&bios_path).unwrap();
 
     // pass the disk image paths as env variables to the main.rs
     println!("cargo:rustc-env=UEFI_PATH={}", uefi_path.display());
     println!("cargo:rustc-env=BIOS_PATH={}", bios_path.display());
-}
\ No newline at end of file
+}
+
+/// Copies the kernel into `out_dir` with its symbol table filled in, so that backtraces can
+/// name functions. Returns the kernel unchanged if it has no room for the table.
+fn embed_symbol_table(kernel: &Path, out_dir: &Path) -> PathBuf {
+    let mut image = std::fs::read(kernel).unwrap();
+    let elf = object::File::parse(&*image).unwrap();
+    let Some(section) = elf.section_by_name(SYMBOL_TABLE_SECTION) else {
+        println!("cargo:warning=no {SYMBOL_TABLE_SECTION} section, backtraces show only addresses");
+        return kernel.to_owned();
+    };
+    let (offset, size) = section.file_range().unwrap();
+    let (offset, size) = (offset as usize, size as usize);
+    assert!(
+        image[offset..].starts_with(SYMBOL_TABLE_EMPTY_MAGIC),
+        "{SYMBOL_TABLE_SECTION} does not hold an empty symbol table"
+    );
+    let link_address = section.address();
+    let mut symbols: Vec<(u64, u64, String)> = elf
+        .symbols()
+        .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.size() > 0)
+        .filter_map(|symbol| {
+            let name = rustc_demangle::demangle(symbol.name().ok()?);
+            // the alternate format leaves out the hash at the end of each name
+            Some((symbol.address(), symbol.size(), format!("{name:#}")))
+        })
+        .collect();
+    symbols.sort_by_key(|&(address, ..)| address);
+    symbols.dedup_by_key(|&mut (address, ..)| address);
+
+    let table = encode_symbol_table(link_address, &symbols, size);
+    image[offset..offset + table.len()].copy_from_slice(&table);
+    let patched = out_dir.join("kernel_with_symbols");
+    std::fs::write(&patched, image).unwrap();
+    patched
+}
+
+/// Lays out `symbols` (address, size and name, sorted by address) in at most `capacity` bytes,
+/// leaving out the last ones if they do not fit.
+fn encode_symbol_table(
+    link_address: u64,
+    symbols: &[(u64, u64, String)],
+    capacity: usize,
+) -> Vec<u8> {
+    let mut count = 0;
+    let mut names_size = 0;
+    for (_, _, name) in symbols {
+        let entries_size = (count + 1) * SYMBOL_TABLE_ENTRY_SIZE;
+        if SYMBOL_TABLE_HEADER_SIZE + entries_size + names_size + name.len() > capacity {
+            println!(
+                "cargo:warning=only {count} of {} kernel symbols fit into the symbol table",
+                symbols.len()
+            );
+            break;
+        }
+        count += 1;
+        names_size += name.len();
+    }
+
+    let mut table = Vec::with_capacity(capacity);
+    table.extend_from_slice(SYMBOL_TABLE_MAGIC);
+    table.extend_from_slice(&link_address.to_le_bytes());
+    table.extend_from_slice(&(count as u32).to_le_bytes());
+    table.extend_from_slice(&[0; 4]);
+    let mut name_offset = 0;
+    for (address, size, name) in &symbols[..count] {
+        table.extend_from_slice(&address.to_le_bytes());
+        table.extend_from_slice(&(*size as u32).to_le_bytes());
+        table.extend_from_slice(&(name_offset as u32).to_le_bytes());
+        table.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        name_offset += name.len();
+    }
+    for (_, _, name) in &symbols[..count] {
+        table.extend_from_slice(name.as_bytes());
+    }
+    table
+}
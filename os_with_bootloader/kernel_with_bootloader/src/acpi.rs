@@ -0,0 +1,247 @@
+mod fadt;
+mod hpet;
+mod madt;
+mod mcfg;
+pub mod power;
+
+pub use fadt::Fadt;
+pub use hpet::Hpet;
+pub use madt::Madt;
+pub use mcfg::Mcfg;
+
+use core::{mem, ptr, slice};
+
+use spin::Once;
+use x86_64::PhysAddr;
+
+use crate::memory::phys_to_virt;
+
+/// Root System Description Pointer, as found at `boot_info.rsdp_addr`.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    // the fields below only exist from ACPI 2.0 (revision 2) on
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+/// Size of the ACPI 1.0 part of [Rsdp] that the first checksum covers.
+const RSDP_V1_LENGTH: usize = 20;
+
+/// Header shared by every system description table.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    /// The bootloader did not find an RSDP.
+    NoRsdp,
+    InvalidRsdp,
+    /// The table with this signature failed its checksum.
+    InvalidChecksum([u8; 4]),
+    /// The table with this signature is shorter than its own header or the fields it must have.
+    InvalidLength([u8; 4]),
+    TableNotFound([u8; 4]),
+}
+
+/// The RSDT or XSDT, whichever the firmware provides.
+struct RootTable {
+    address: PhysAddr,
+    /// 4 for the RSDT, 8 for the XSDT.
+    entry_size: usize,
+    entry_count: usize,
+}
+
+static ROOT_TABLE: Once<RootTable> = Once::new();
+
+/// The tables the kernel understands, parsed once by [init]. A table the firmware does not
+/// provide (or that fails to validate) is `None`.
+#[derive(Debug)]
+pub struct AcpiTables {
+    pub fadt: Option<Fadt>,
+    pub madt: Option<Madt>,
+    pub hpet: Option<Hpet>,
+    pub mcfg: Option<Mcfg>,
+}
+
+static TABLES: Once<AcpiTables> = Once::new();
+
+/// Address space identifiers of a [GenericAddress].
+pub const ADDRESS_SPACE_SYSTEM_MEMORY: u8 = 0;
+pub const ADDRESS_SPACE_SYSTEM_IO: u8 = 1;
+pub const ADDRESS_SPACE_PCI_CONFIG: u8 = 2;
+
+/// ACPI Generic Address Structure: a register in memory, I/O or PCI configuration space.
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// Size of the structure inside a table.
+    const LENGTH: usize = 12;
+
+    fn parse(bytes: &[u8], offset: usize) -> Self {
+        Self {
+            address_space: bytes[offset],
+            bit_width: bytes[offset + 1],
+            bit_offset: bytes[offset + 2],
+            access_size: bytes[offset + 3],
+            address: read_u64(bytes, offset + 4),
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Reads a `T` from physical memory through the physical memory mapping.
+///
+/// # Safety
+/// `addr` must point to at least `size_of::<T>()` readable bytes that form a valid `T`.
+unsafe fn read_phys<T: Copy>(addr: PhysAddr) -> T {
+    ptr::read_unaligned(phys_to_virt(addr).as_ptr())
+}
+
+/// # Safety
+/// `addr..addr + len` must be readable physical memory.
+unsafe fn phys_bytes(addr: PhysAddr, len: usize) -> &'static [u8] {
+    slice::from_raw_parts(phys_to_virt(addr).as_ptr(), len)
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// Validates the RSDP, walks the root table and parses every table listed in [AcpiTables].
+/// [memory::init] must have run.
+///
+/// [memory::init]: crate::memory::init
+pub fn init(rsdp_addr: Option<u64>) -> Result<(), AcpiError> {
+    let rsdp_addr = PhysAddr::new(rsdp_addr.ok_or(AcpiError::NoRsdp)?);
+    let rsdp: Rsdp = unsafe { read_phys(rsdp_addr) };
+    let v1_bytes = unsafe { phys_bytes(rsdp_addr, RSDP_V1_LENGTH) };
+    if &rsdp.signature != b"RSD PTR " || !checksum_ok(v1_bytes) {
+        return Err(AcpiError::InvalidRsdp);
+    }
+
+    let (address, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        if !checksum_ok(unsafe { phys_bytes(rsdp_addr, mem::size_of::<Rsdp>()) }) {
+            return Err(AcpiError::InvalidRsdp);
+        }
+        (PhysAddr::new(rsdp.xsdt_address), 8)
+    } else {
+        (PhysAddr::new(rsdp.rsdt_address as u64), 4)
+    };
+    let header = validate_table(address)?;
+    ROOT_TABLE.call_once(|| RootTable {
+        address,
+        entry_size,
+        entry_count: (header.length as usize - mem::size_of::<SdtHeader>()) / entry_size,
+    });
+
+    let tables = TABLES.call_once(|| AcpiTables {
+        fadt: Fadt::parse().ok(),
+        madt: Madt::parse().ok(),
+        hpet: Hpet::parse().ok(),
+        mcfg: Mcfg::parse().ok(),
+    });
+    log::info!(
+        "ACPI tables: FADT {}, MADT {}, HPET {}, MCFG {}",
+        tables.fadt.is_some(),
+        tables.madt.is_some(),
+        tables.hpet.is_some(),
+        tables.mcfg.is_some()
+    );
+    Ok(())
+}
+
+/// The parsed ACPI tables, once [init] has succeeded.
+pub fn tables() -> Option<&'static AcpiTables> {
+    TABLES.get()
+}
+
+/// Checks the length and checksum of the table at `addr` and returns its header.
+fn validate_table(addr: PhysAddr) -> Result<SdtHeader, AcpiError> {
+    let header: SdtHeader = unsafe { read_phys(addr) };
+    // the length is used to size the table's body, so it must at least cover the header
+    if (header.length as usize) < mem::size_of::<SdtHeader>() {
+        return Err(AcpiError::InvalidLength(header.signature));
+    }
+    if !checksum_ok(unsafe { phys_bytes(addr, header.length as usize) }) {
+        return Err(AcpiError::InvalidChecksum(header.signature));
+    }
+    Ok(header)
+}
+
+/// Calls `f` with the physical address of every table listed in the root table.
+fn for_each_table(mut f: impl FnMut(PhysAddr)) {
+    let Some(root) = ROOT_TABLE.get() else { return };
+    let entries_start = root.address + mem::size_of::<SdtHeader>();
+    for i in 0..root.entry_count {
+        let entry = entries_start + i * root.entry_size;
+        let table = if root.entry_size == 8 {
+            unsafe { read_phys::<u64>(entry) }
+        } else {
+            unsafe { read_phys::<u32>(entry) as u64 }
+        };
+        f(PhysAddr::new(table));
+    }
+}
+
+/// Finds the table with the given signature and returns its address and the bytes that follow
+/// its header.
+pub fn find_table(signature: &[u8; 4]) -> Result<(PhysAddr, &'static [u8]), AcpiError> {
+    let mut found = None;
+    for_each_table(|addr| {
+        if found.is_none() && &unsafe { read_phys::<SdtHeader>(addr) }.signature == signature {
+            found = Some(addr);
+        }
+    });
+    let addr = found.ok_or(AcpiError::TableNotFound(*signature))?;
+    Ok((addr, table_body(addr)?))
+}
+
+/// Validates the table at `addr` and returns the bytes that follow its header. Used for tables
+/// that are not listed in the root table, such as the DSDT.
+pub fn table_body(addr: PhysAddr) -> Result<&'static [u8], AcpiError> {
+    let header = validate_table(addr)?;
+    Ok(unsafe {
+        phys_bytes(
+            addr + mem::size_of::<SdtHeader>(),
+            header.length as usize - mem::size_of::<SdtHeader>(),
+        )
+    })
+}
@@ -0,0 +1,95 @@
+use super::{find_table, read_u16, read_u32, read_u64, AcpiError, GenericAddress};
+
+/// Offsets below are from the start of the table, as in the ACPI specification.
+const HEADER_LENGTH: usize = 36;
+
+const DSDT: usize = 40;
+const SCI_INTERRUPT: usize = 46;
+const SMI_COMMAND: usize = 48;
+const ACPI_ENABLE: usize = 52;
+const ACPI_DISABLE: usize = 53;
+const PM1A_EVENT_BLOCK: usize = 56;
+const PM1B_EVENT_BLOCK: usize = 60;
+const PM1A_CONTROL_BLOCK: usize = 64;
+const PM1B_CONTROL_BLOCK: usize = 68;
+const PM_TIMER_BLOCK: usize = 76;
+const CENTURY: usize = 108;
+const BOOT_ARCHITECTURE_FLAGS: usize = 109;
+const FLAGS: usize = 112;
+const RESET_REGISTER: usize = 116;
+const RESET_VALUE: usize = 128;
+const X_DSDT: usize = 140;
+
+/// `FLAGS` bit telling that [Fadt::reset_register] may be used to reset the system.
+const RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+/// `BOOT_ARCHITECTURE_FLAGS` bit telling that the system has an 8042 keyboard controller.
+const HAS_8042: u16 = 1 << 1;
+
+/// The parts of the Fixed ACPI Description Table ("FACP") the kernel uses.
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub struct Fadt {
+    /// Physical address of the Differentiated System Description Table.
+    pub dsdt_address: u64,
+    /// Legacy IRQ line the System Control Interrupt is wired to.
+    pub sci_interrupt: u16,
+    /// I/O port that switches the chipset between legacy and ACPI mode, 0 if there is none.
+    pub smi_command_port: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_event_block: u32,
+    pub pm1b_event_block: u32,
+    pub pm1a_control_block: u32,
+    pub pm1b_control_block: u32,
+    pub pm_timer_block: u32,
+    /// CMOS register holding the century, 0 if the RTC does not have one.
+    pub century_register: u8,
+    pub boot_architecture_flags: u16,
+    pub flags: u32,
+    /// Register to write [Fadt::reset_value] to for a reset, if the firmware supports it.
+    pub reset_register: Option<GenericAddress>,
+    pub reset_value: u8,
+}
+
+impl Fadt {
+    pub fn parse() -> Result<Fadt, AcpiError> {
+        let (_, body) = find_table(b"FACP")?;
+        // the table grew with every ACPI revision, so fields past its end read as 0
+        let field = |offset: usize, size: usize| {
+            body.get(offset - HEADER_LENGTH..offset - HEADER_LENGTH + size)
+        };
+        let u8_at = |offset| field(offset, 1).map_or(0, |bytes| bytes[0]);
+        let u16_at = |offset| field(offset, 2).map_or(0, |bytes| read_u16(bytes, 0));
+        let u32_at = |offset| field(offset, 4).map_or(0, |bytes| read_u32(bytes, 0));
+
+        let flags = u32_at(FLAGS);
+        let x_dsdt = field(X_DSDT, 8).map_or(0, |bytes| read_u64(bytes, 0));
+        let reset_supported = flags & RESET_REGISTER_SUPPORTED != 0
+            && field(RESET_REGISTER, GenericAddress::LENGTH).is_some()
+            && field(RESET_VALUE, 1).is_some();
+        Ok(Fadt {
+            dsdt_address: if x_dsdt != 0 { x_dsdt } else { u32_at(DSDT) as u64 },
+            sci_interrupt: u16_at(SCI_INTERRUPT),
+            smi_command_port: u32_at(SMI_COMMAND),
+            acpi_enable: u8_at(ACPI_ENABLE),
+            acpi_disable: u8_at(ACPI_DISABLE),
+            pm1a_event_block: u32_at(PM1A_EVENT_BLOCK),
+            pm1b_event_block: u32_at(PM1B_EVENT_BLOCK),
+            pm1a_control_block: u32_at(PM1A_CONTROL_BLOCK),
+            pm1b_control_block: u32_at(PM1B_CONTROL_BLOCK),
+            pm_timer_block: u32_at(PM_TIMER_BLOCK),
+            century_register: u8_at(CENTURY),
+            boot_architecture_flags: u16_at(BOOT_ARCHITECTURE_FLAGS),
+            flags,
+            reset_register: reset_supported
+                .then(|| GenericAddress::parse(body, RESET_REGISTER - HEADER_LENGTH)),
+            reset_value: u8_at(RESET_VALUE),
+        })
+    }
+
+    /// Whether the firmware reports an 8042 keyboard controller. ACPI 1.0 tables do not have
+    /// the flag, so there it is assumed to be present.
+    pub fn has_8042(&self) -> bool {
+        self.boot_architecture_flags == 0 || self.boot_architecture_flags & HAS_8042 != 0
+    }
+}
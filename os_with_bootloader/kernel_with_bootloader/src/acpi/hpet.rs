@@ -0,0 +1,40 @@
+use super::{find_table, read_u16, read_u32, AcpiError, GenericAddress};
+
+/// The High Precision Event Timer description table ("HPET").
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub struct Hpet {
+    pub hardware_revision: u8,
+    /// Number of comparators (timers) in the block.
+    pub comparator_count: u8,
+    /// The main counter is 64 bits wide rather than 32.
+    pub counter_64bit: bool,
+    /// The HPET can take over the PIT and RTC interrupt lines.
+    pub legacy_replacement: bool,
+    pub pci_vendor_id: u16,
+    /// Where the HPET registers live, normally in system memory.
+    pub base_address: GenericAddress,
+    pub hpet_number: u8,
+    /// Minimum main counter ticks between periodic interrupts without losing any.
+    pub minimum_tick: u16,
+}
+
+impl Hpet {
+    pub fn parse() -> Result<Hpet, AcpiError> {
+        let (_, body) = find_table(b"HPET")?;
+        if body.len() < 19 {
+            return Err(AcpiError::InvalidLength(*b"HPET"));
+        }
+        let block_id = read_u32(body, 0);
+        Ok(Hpet {
+            hardware_revision: block_id as u8,
+            comparator_count: ((block_id >> 8) & 0x1F) as u8 + 1,
+            counter_64bit: block_id & (1 << 13) != 0,
+            legacy_replacement: block_id & (1 << 15) != 0,
+            pci_vendor_id: (block_id >> 16) as u16,
+            base_address: GenericAddress::parse(body, 4),
+            hpet_number: body[16],
+            minimum_tick: read_u16(body, 17),
+        })
+    }
+}
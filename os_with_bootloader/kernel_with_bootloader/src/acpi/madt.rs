@@ -0,0 +1,129 @@
+use super::{find_table, read_u16, read_u32, read_u64, AcpiError};
+
+/// Upper bounds for the entries kept from the MADT.
+const MAX_IO_APICS: usize = 8;
+const MAX_OVERRIDES: usize = 16;
+
+// MADT entry types
+const PROCESSOR_LOCAL_APIC: u8 = 0;
+const IO_APIC: u8 = 1;
+const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+
+/// An I/O APIC and the first global system interrupt (GSI) it handles.
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicInfo {
+    pub id: u8,
+    pub address: u32,
+    pub gsi_base: u32,
+}
+
+/// Records that ISA IRQ `source` is wired to global system interrupt `gsi` instead of the
+/// identity-mapped one, possibly with a different polarity or trigger mode.
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptSourceOverride {
+    pub source: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+impl InterruptSourceOverride {
+    /// ISA interrupts are active high unless the override says otherwise.
+    pub fn active_low(&self) -> bool {
+        self.flags & 0b11 == 0b11
+    }
+
+    /// ISA interrupts are edge triggered unless the override says otherwise.
+    pub fn level_triggered(&self) -> bool {
+        (self.flags >> 2) & 0b11 == 0b11
+    }
+}
+
+/// The parts of the Multiple APIC Description Table the kernel uses.
+#[derive(Debug, Clone, Copy)]
+pub struct Madt {
+    pub local_apic_address: u64,
+    /// The system also has 8259 PICs, which must be masked when the APICs are used.
+    pub has_legacy_pics: bool,
+    /// Number of enabled processors.
+    pub processor_count: usize,
+    io_apics: [Option<IoApicInfo>; MAX_IO_APICS],
+    overrides: [Option<InterruptSourceOverride>; MAX_OVERRIDES],
+}
+
+impl Madt {
+    /// Finds and parses the MADT ("APIC" signature).
+    pub fn parse() -> Result<Madt, AcpiError> {
+        let (_, body) = find_table(b"APIC")?;
+        if body.len() < 8 {
+            return Err(AcpiError::InvalidLength(*b"APIC"));
+        }
+        let mut madt = Madt {
+            local_apic_address: read_u32(body, 0) as u64,
+            has_legacy_pics: read_u32(body, 4) & 1 != 0,
+            processor_count: 0,
+            io_apics: [None; MAX_IO_APICS],
+            overrides: [None; MAX_OVERRIDES],
+        };
+
+        let mut entries = &body[8..];
+        while entries.len() >= 2 {
+            let (kind, length) = (entries[0], entries[1] as usize);
+            if length < 2 || length > entries.len() {
+                break;
+            }
+            let entry = &entries[..length];
+            entries = &entries[length..];
+            // entries too short for the fields read from them are skipped
+            let minimum = match kind {
+                PROCESSOR_LOCAL_APIC => 8,
+                INTERRUPT_SOURCE_OVERRIDE => 10,
+                IO_APIC | LOCAL_APIC_ADDRESS_OVERRIDE => 12,
+                _ => 2,
+            };
+            if length < minimum {
+                continue;
+            }
+            match kind {
+                PROCESSOR_LOCAL_APIC if read_u32(entry, 4) & 1 != 0 => {
+                    madt.processor_count += 1;
+                }
+                IO_APIC => push(
+                    &mut madt.io_apics,
+                    IoApicInfo {
+                        id: entry[2],
+                        address: read_u32(entry, 4),
+                        gsi_base: read_u32(entry, 8),
+                    },
+                ),
+                INTERRUPT_SOURCE_OVERRIDE => push(
+                    &mut madt.overrides,
+                    InterruptSourceOverride {
+                        source: entry[3],
+                        gsi: read_u32(entry, 4),
+                        flags: read_u16(entry, 8),
+                    },
+                ),
+                LOCAL_APIC_ADDRESS_OVERRIDE => madt.local_apic_address = read_u64(entry, 4),
+                _ => {}
+            }
+        }
+        Ok(madt)
+    }
+
+    pub fn io_apics(&self) -> impl Iterator<Item = &IoApicInfo> {
+        self.io_apics.iter().flatten()
+    }
+
+    /// The override for ISA IRQ `irq`, if the firmware reports one.
+    pub fn interrupt_override(&self, irq: u8) -> Option<&InterruptSourceOverride> {
+        self.overrides.iter().flatten().find(|o| o.source == irq)
+    }
+}
+
+/// Stores `value` in the first free slot; entries beyond the capacity are ignored.
+fn push<T>(slots: &mut [Option<T>], value: T) {
+    if let Some(slot) = slots.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(value);
+    }
+}
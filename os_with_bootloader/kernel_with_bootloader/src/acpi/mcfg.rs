@@ -0,0 +1,61 @@
+use super::{find_table, read_u16, read_u64, AcpiError};
+
+/// Number of configuration space ranges kept from the table.
+const MAX_ENTRIES: usize = 8;
+const ENTRY_LENGTH: usize = 16;
+
+/// A range of PCI buses whose configuration space is memory mapped at `base_address`.
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub struct McfgEntry {
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+}
+
+impl McfgEntry {
+    /// Physical address of the configuration space of `bus:device.function`, if this entry
+    /// covers the bus.
+    #[allow(dead_code)]
+    pub fn config_address(&self, bus: u8, device: u8, function: u8) -> Option<u64> {
+        if !(self.start_bus..=self.end_bus).contains(&bus) {
+            return None;
+        }
+        let offset = ((bus - self.start_bus) as u64) << 20
+            | (device as u64 & 0x1F) << 15
+            | (function as u64 & 0x7) << 12;
+        Some(self.base_address + offset)
+    }
+}
+
+/// The PCI Express memory mapped configuration table ("MCFG").
+#[derive(Debug, Clone, Copy)]
+pub struct Mcfg {
+    entries: [Option<McfgEntry>; MAX_ENTRIES],
+}
+
+impl Mcfg {
+    pub fn parse() -> Result<Mcfg, AcpiError> {
+        let (_, body) = find_table(b"MCFG")?;
+        let mut mcfg = Mcfg {
+            entries: [None; MAX_ENTRIES],
+        };
+        // 8 reserved bytes precede the entries
+        let entries = body.get(8..).unwrap_or_default().chunks_exact(ENTRY_LENGTH);
+        for (slot, entry) in mcfg.entries.iter_mut().zip(entries) {
+            *slot = Some(McfgEntry {
+                base_address: read_u64(entry, 0),
+                segment_group: read_u16(entry, 8),
+                start_bus: entry[10],
+                end_bus: entry[11],
+            });
+        }
+        Ok(mcfg)
+    }
+
+    #[allow(dead_code)]
+    pub fn entries(&self) -> impl Iterator<Item = &McfgEntry> {
+        self.entries.iter().flatten()
+    }
+}
@@ -0,0 +1,180 @@
+use core::{hint, ptr};
+
+use x86_64::instructions::port::Port;
+use x86_64::instructions::tables::lidt;
+use x86_64::instructions::{hlt, interrupts};
+use x86_64::structures::DescriptorTablePointer;
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::{
+    table_body, tables, Fadt, GenericAddress, ADDRESS_SPACE_PCI_CONFIG, ADDRESS_SPACE_SYSTEM_IO,
+    ADDRESS_SPACE_SYSTEM_MEMORY,
+};
+use crate::memory::phys_to_virt;
+
+// AML opcodes needed to find the `_S5_` package in the DSDT
+const NAME_OP: u8 = 0x08;
+const PACKAGE_OP: u8 = 0x12;
+const BYTE_PREFIX: u8 = 0x0A;
+const ROOT_CHAR: u8 = b'\\';
+
+// PM1 control register bits
+const SCI_ENABLED: u16 = 1 << 0;
+const SLEEP_TYPE_SHIFT: u16 = 10;
+const SLEEP_ENABLE: u16 = 1 << 13;
+
+const KEYBOARD_CONTROLLER_STATUS: u16 = 0x64;
+const KEYBOARD_CONTROLLER_INPUT_FULL: u8 = 1 << 1;
+const KEYBOARD_CONTROLLER_RESET: u8 = 0xFE;
+
+const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+const PCI_CONFIG_DATA: u16 = 0xCFC;
+
+/// How long to poll the hardware before giving up on it.
+const POLL_ITERATIONS: usize = 1_000_000;
+
+fn fadt() -> Option<&'static Fadt> {
+    tables()?.fadt.as_ref()
+}
+
+/// Extracts the SLP_TYPa and SLP_TYPb values of the S5 (soft off) state from the DSDT's
+/// `Name (_S5_, Package () { a, b, ... })`, without a full AML interpreter.
+fn s5_sleep_types(dsdt: &[u8]) -> Option<(u16, u16)> {
+    let position = dsdt.windows(4).position(|name| name == b"_S5_")?;
+    let is_name = (position >= 1 && dsdt[position - 1] == NAME_OP)
+        || (position >= 2 && dsdt[position - 2] == NAME_OP && dsdt[position - 1] == ROOT_CHAR);
+    if !is_name || dsdt.get(position + 4) != Some(&PACKAGE_OP) {
+        return None;
+    }
+    // skip the PkgLength, whose lead byte tells how many bytes follow it, and NumElements
+    let mut index = position + 5;
+    index += (*dsdt.get(index)? >> 6) as usize + 2;
+
+    // ZeroOp and OneOp encode 0 and 1 directly, everything else is a BytePrefix constant
+    let mut next_value = || {
+        if *dsdt.get(index)? == BYTE_PREFIX {
+            index += 1;
+        }
+        let value = *dsdt.get(index)?;
+        index += 1;
+        Some(value as u16)
+    };
+    Some((next_value()?, next_value()?))
+}
+
+/// Switches the chipset from legacy to ACPI mode if the firmware left it in legacy mode.
+fn enable_acpi_mode(fadt: &Fadt) {
+    let mut pm1a_control = Port::<u16>::new(fadt.pm1a_control_block as u16);
+    if unsafe { pm1a_control.read() } & SCI_ENABLED != 0
+        || fadt.smi_command_port == 0
+        || fadt.acpi_enable == 0
+    {
+        return;
+    }
+    unsafe { Port::<u8>::new(fadt.smi_command_port as u16).write(fadt.acpi_enable) };
+    for _ in 0..POLL_ITERATIONS {
+        if unsafe { pm1a_control.read() } & SCI_ENABLED != 0 {
+            return;
+        }
+        hint::spin_loop();
+    }
+}
+
+/// Puts the machine into the S5 soft-off state. Halts forever if that fails.
+pub fn shutdown() -> ! {
+    if let Some(fadt) = fadt() {
+        let sleep_types = table_body(PhysAddr::new(fadt.dsdt_address))
+            .ok()
+            .and_then(s5_sleep_types);
+        match sleep_types {
+            Some((sleep_type_a, sleep_type_b)) => {
+                enable_acpi_mode(fadt);
+                interrupts::disable();
+                unsafe {
+                    Port::<u16>::new(fadt.pm1a_control_block as u16)
+                        .write(sleep_type_a << SLEEP_TYPE_SHIFT | SLEEP_ENABLE);
+                    if fadt.pm1b_control_block != 0 {
+                        Port::<u16>::new(fadt.pm1b_control_block as u16)
+                            .write(sleep_type_b << SLEEP_TYPE_SHIFT | SLEEP_ENABLE);
+                    }
+                }
+                for _ in 0..POLL_ITERATIONS {
+                    hint::spin_loop();
+                }
+            }
+            None => log::error!("no _S5_ object in the DSDT"),
+        }
+    }
+    log::error!("ACPI power-off failed, it is now safe to turn off the computer");
+    interrupts::disable();
+    loop {
+        hlt();
+    }
+}
+
+/// Writes `value` to an ACPI register, as the FADT reset register is specified.
+fn write_register(register: &GenericAddress, value: u8) {
+    match register.address_space {
+        ADDRESS_SPACE_SYSTEM_IO => unsafe { Port::<u8>::new(register.address as u16).write(value) },
+        ADDRESS_SPACE_SYSTEM_MEMORY => unsafe {
+            let addr = phys_to_virt(PhysAddr::new(register.address));
+            ptr::write_volatile(addr.as_mut_ptr::<u8>(), value);
+        },
+        ADDRESS_SPACE_PCI_CONFIG => {
+            // the register is on bus 0; the address encodes device, function and offset
+            let device = (register.address >> 32) as u32 & 0x1F;
+            let function = (register.address >> 16) as u32 & 0x7;
+            let offset = register.address as u32 & 0xFF;
+            let config_address = 1 << 31 | device << 11 | function << 8 | (offset & 0xFC);
+            unsafe {
+                Port::<u32>::new(PCI_CONFIG_ADDRESS).write(config_address);
+                Port::<u8>::new(PCI_CONFIG_DATA + (offset & 0b11) as u16).write(value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Pulses the CPU reset line through the 8042 keyboard controller.
+fn pulse_8042_reset() {
+    let mut status = Port::<u8>::new(KEYBOARD_CONTROLLER_STATUS);
+    for _ in 0..POLL_ITERATIONS {
+        if unsafe { status.read() } & KEYBOARD_CONTROLLER_INPUT_FULL == 0 {
+            break;
+        }
+        hint::spin_loop();
+    }
+    unsafe { status.write(KEYBOARD_CONTROLLER_RESET) };
+}
+
+/// Resets the machine through the FADT reset register, falling back to the 8042 and finally to
+/// a triple fault.
+pub fn reboot() -> ! {
+    interrupts::disable();
+    let fadt = fadt();
+    if let Some(fadt) = fadt {
+        if let Some(reset_register) = &fadt.reset_register {
+            write_register(reset_register, fadt.reset_value);
+            for _ in 0..POLL_ITERATIONS {
+                hint::spin_loop();
+            }
+        }
+    }
+    if fadt.is_none_or(Fadt::has_8042) {
+        pulse_8042_reset();
+        for _ in 0..POLL_ITERATIONS {
+            hint::spin_loop();
+        }
+    }
+
+    // with an empty IDT, the breakpoint escalates to a triple fault, which resets the CPU
+    let empty_idt = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::zero(),
+    };
+    unsafe { lidt(&empty_idt) };
+    x86_64::instructions::interrupts::int3();
+    loop {
+        hlt();
+    }
+}
@@ -0,0 +1,241 @@
+//! The kernel heap, which makes the `alloc` crate's collections available.
+//!
+//! The allocator behind it is chosen with a cargo feature: `heap_bump`, `heap_linked_list`
+//! (the default), `heap_slab` or `heap_buddy`. Each keeps the same [HeapStats], and
+//! `heap_debug` additionally records where every live allocation was made and poisons freed
+//! memory. Once its table is full, frees it cannot match to a record are not carried out.
+
+#[cfg(feature = "heap_buddy")]
+mod buddy;
+#[cfg(feature = "heap_bump")]
+mod bump;
+#[cfg(not(any(feature = "heap_bump", feature = "heap_buddy")))]
+mod linked_list;
+#[cfg(feature = "heap_slab")]
+mod slab;
+#[cfg(feature = "heap_debug")]
+mod tracking;
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::structures::paging::PageTableFlags;
+
+use crate::memory::paging::PagingError;
+use crate::memory::vmalloc;
+
+#[cfg(feature = "heap_debug")]
+pub use tracking::{live_allocations, mark};
+
+#[cfg(any(
+    all(feature = "heap_bump", feature = "heap_linked_list"),
+    all(feature = "heap_bump", feature = "heap_slab"),
+    all(feature = "heap_bump", feature = "heap_buddy"),
+    all(feature = "heap_linked_list", feature = "heap_slab"),
+    all(feature = "heap_linked_list", feature = "heap_buddy"),
+    all(feature = "heap_slab", feature = "heap_buddy"),
+))]
+compile_error!("choose at most one of the heap_* allocator features");
+
+#[cfg(feature = "heap_bump")]
+type Selected = bump::BumpAllocator;
+#[cfg(feature = "heap_slab")]
+type Selected = slab::SlabAllocator;
+#[cfg(feature = "heap_buddy")]
+type Selected = buddy::BuddyAllocator;
+#[cfg(not(any(feature = "heap_bump", feature = "heap_slab", feature = "heap_buddy")))]
+type Selected = linked_list::LinkedListAllocator;
+
+/// Size of the kernel heap. All of it is backed by frames from the start.
+pub const HEAP_SIZE: usize = 16 * 1024 * 1024;
+
+/// A strategy for handing out the heap's memory.
+trait HeapAllocator {
+    /// Shown in the heap statistics.
+    const NAME: &'static str;
+    /// An allocator without memory, until [HeapAllocator::init].
+    const EMPTY: Self;
+
+    /// Takes over the `size` bytes at `start`, which is aligned to a page.
+    ///
+    /// # Safety
+    /// The memory must be unused and stay reserved for the allocator.
+    unsafe fn init(&mut self, start: *mut u8, size: usize);
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// # Safety
+    /// `ptr` must have been returned by [HeapAllocator::allocate] for the same `layout`, and
+    /// not have been deallocated since.
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Bytes available for allocations, and the largest allocation that would still succeed.
+    fn free_space(&self) -> (usize, usize);
+}
+
+/// Number of size classes allocations are counted in: powers of two from 8 to 4096 bytes, and
+/// everything larger.
+pub const SIZE_CLASSES: usize = 11;
+
+/// Largest allocation counted in size class `class`, `None` for the last one.
+pub fn size_class_limit(class: usize) -> Option<usize> {
+    (class + 1 < SIZE_CLASSES).then(|| 8 << class)
+}
+
+fn size_class(size: usize) -> usize {
+    (size.next_power_of_two().trailing_zeros() as usize)
+        .saturating_sub(3)
+        .min(SIZE_CLASSES - 1)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    /// Name of the allocator in use.
+    pub allocator: &'static str,
+    pub size: usize,
+    /// Bytes requested by allocations that are still live.
+    pub in_use: usize,
+    /// Highest `in_use` so far.
+    pub peak: usize,
+    /// Bytes the allocator has left. With bump, slab and buddy allocators this is less than
+    /// `size - in_use`, since they hand out more than is asked for or never reuse memory.
+    pub free: usize,
+    /// The largest allocation that would still succeed.
+    pub largest_free: usize,
+    pub allocations: u64,
+    pub frees: u64,
+    /// Allocations that could not be served.
+    pub failures: u64,
+    /// Live allocations in each size class, see [size_class_limit].
+    pub live_by_class: [usize; SIZE_CLASSES],
+}
+
+impl HeapStats {
+    /// How much of the free memory cannot be used for one large allocation, in percent.
+    pub fn fragmentation(&self) -> usize {
+        match self.free {
+            0 => 0,
+            free => 100 - self.largest_free * 100 / free,
+        }
+    }
+}
+
+/// The selected allocator and what the heap keeps track of around it.
+struct Heap<A> {
+    allocator: A,
+    stats: HeapStats,
+    #[cfg(feature = "heap_debug")]
+    tracker: tracking::Tracker,
+}
+
+impl<A: HeapAllocator> Heap<A> {
+    fn stats(&self) -> HeapStats {
+        let (free, largest_free) = self.allocator.free_space();
+        HeapStats {
+            free,
+            largest_free,
+            ..self.stats
+        }
+    }
+}
+
+/// The global allocator. Interrupts stay disabled while it is locked, so that interrupt
+/// handlers can allocate without deadlocking against the code they interrupted.
+struct KernelHeap(Mutex<Heap<Selected>>);
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "heap_debug")]
+        let callers = crate::backtrace::Frames::here();
+        interrupts::without_interrupts(|| {
+            let mut heap = self.0.lock();
+            let Some(ptr) = heap.allocator.allocate(layout) else {
+                heap.stats.failures += 1;
+                return ptr::null_mut();
+            };
+            let stats = &mut heap.stats;
+            stats.allocations += 1;
+            stats.in_use += layout.size();
+            stats.peak = stats.peak.max(stats.in_use);
+            stats.live_by_class[size_class(layout.size())] += 1;
+            #[cfg(feature = "heap_debug")]
+            heap.tracker.record(ptr, layout.size(), callers);
+            ptr.as_ptr()
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let ptr = NonNull::new(ptr).expect("freeing a null pointer");
+        interrupts::without_interrupts(|| {
+            let mut heap = self.0.lock();
+            #[cfg(feature = "heap_debug")]
+            {
+                if !heap.tracker.forget(ptr) {
+                    // possibly a double free, which would corrupt the free lists, so the block
+                    // is left allocated and keeps counting as in use
+                    return;
+                }
+                // use after free shows up as this pattern
+                ptr.as_ptr().write_bytes(tracking::FREED_POISON, layout.size());
+            }
+            heap.allocator.deallocate(ptr, layout);
+            let stats = &mut heap.stats;
+            stats.frees += 1;
+            stats.in_use -= layout.size();
+            stats.live_by_class[size_class(layout.size())] -= 1;
+        });
+    }
+}
+
+#[global_allocator]
+static HEAP: KernelHeap = KernelHeap(Mutex::new(Heap {
+    allocator: Selected::EMPTY,
+    stats: HeapStats {
+        allocator: Selected::NAME,
+        size: 0,
+        in_use: 0,
+        peak: 0,
+        free: 0,
+        largest_free: 0,
+        allocations: 0,
+        frees: 0,
+        failures: 0,
+        live_by_class: [0; SIZE_CLASSES],
+    },
+    #[cfg(feature = "heap_debug")]
+    tracker: tracking::Tracker::EMPTY,
+}));
+
+/// Runs `f` with the heap locked and interrupts disabled.
+fn with_heap<R>(f: impl FnOnce(&mut Heap<Selected>) -> R) -> R {
+    interrupts::without_interrupts(|| f(&mut HEAP.0.lock()))
+}
+
+/// Maps the heap into the vmalloc area. Allocations fail until this has run.
+pub fn init() -> Result<(), PagingError> {
+    let start = vmalloc::vmalloc(HEAP_SIZE, PageTableFlags::WRITABLE)?;
+    with_heap(|heap| {
+        // the memory was mapped for the heap alone just now
+        unsafe { heap.allocator.init(start.as_mut_ptr(), HEAP_SIZE) };
+        heap.stats.size = HEAP_SIZE;
+    });
+    Ok(())
+}
+
+pub fn stats() -> HeapStats {
+    with_heap(|heap| heap.stats())
+}
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    let stats = stats();
+    panic!(
+        "cannot allocate {} bytes aligned to {} on the heap ({} bytes free, largest block {})",
+        layout.size(),
+        layout.align(),
+        stats.free,
+        stats.largest_free
+    )
+}
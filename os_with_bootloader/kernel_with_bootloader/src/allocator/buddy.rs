@@ -0,0 +1,144 @@
+//! Buddy allocation: memory is split into blocks of power-of-two sizes, each allocation gets the
+//! smallest block that fits, and a freed block is merged with its buddy (the other half of the
+//! block both were split from) whenever that is free too.
+
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+use x86_64::structures::paging::{PageSize, Size4KiB};
+
+use super::HeapAllocator;
+
+/// Order of the smallest block, 16 bytes, which holds a [FreeBlock].
+const MIN_ORDER: u32 = 4;
+/// Number of block sizes, so the largest block is 2^27 bytes (128 MiB).
+const ORDERS: usize = 24;
+
+/// Header written into each free block.
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+pub struct BuddyAllocator {
+    /// Start of the heap, which block addresses are relative to.
+    base: usize,
+    /// Free blocks of each order, starting with [MIN_ORDER].
+    free_lists: [*mut FreeBlock; ORDERS],
+}
+
+// the allocator owns the memory its pointers point into
+unsafe impl Send for BuddyAllocator {}
+
+fn block_size(list: usize) -> usize {
+    1 << (MIN_ORDER as usize + list)
+}
+
+/// Index of the free list with the smallest blocks that fit `layout`.
+fn list_for(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(block_size(0)).next_power_of_two();
+    let list = (size.trailing_zeros() - MIN_ORDER) as usize;
+    (list < ORDERS).then_some(list)
+}
+
+impl BuddyAllocator {
+    fn push(&mut self, list: usize, address: usize) {
+        let block = address as *mut FreeBlock;
+        // the block is free memory of the heap
+        unsafe {
+            block.write(FreeBlock {
+                next: self.free_lists[list],
+            })
+        };
+        self.free_lists[list] = block;
+    }
+
+    fn pop(&mut self, list: usize) -> Option<usize> {
+        let block = NonNull::new(self.free_lists[list])?;
+        self.free_lists[list] = unsafe { block.as_ref().next };
+        Some(block.as_ptr() as usize)
+    }
+
+    /// Takes the block at `address` out of free list `list`, if it is in there.
+    fn remove(&mut self, list: usize, address: usize) -> bool {
+        let mut link: *mut *mut FreeBlock = &mut self.free_lists[list];
+        // every link points at a valid FreeBlock or is null
+        unsafe {
+            while !(*link).is_null() {
+                if *link as usize == address {
+                    *link = (**link).next;
+                    return true;
+                }
+                link = &mut (**link).next;
+            }
+        }
+        false
+    }
+
+    fn blocks(&self, list: usize) -> usize {
+        let mut count = 0;
+        let mut block = self.free_lists[list];
+        while !block.is_null() {
+            count += 1;
+            block = unsafe { (*block).next };
+        }
+        count
+    }
+}
+
+impl HeapAllocator for BuddyAllocator {
+    const NAME: &'static str = "buddy";
+    const EMPTY: Self = Self {
+        base: 0,
+        free_lists: [ptr::null_mut(); ORDERS],
+    };
+
+    unsafe fn init(&mut self, start: *mut u8, size: usize) {
+        self.base = start as usize;
+        // cut the heap into the largest blocks that are aligned relative to its start
+        let mut offset = 0;
+        while size - offset >= block_size(0) {
+            let list = (0..ORDERS)
+                .rev()
+                .find(|&list| offset % block_size(list) == 0 && size - offset >= block_size(list))
+                .expect("the smallest block fits");
+            self.push(list, self.base + offset);
+            offset += block_size(list);
+        }
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        // blocks are aligned relative to the heap, which is only aligned to a page
+        if layout.align() > Size4KiB::SIZE as usize {
+            return None;
+        }
+        let wanted = list_for(layout)?;
+        let mut list = (wanted..ORDERS).find(|&list| !self.free_lists[list].is_null())?;
+        let block = self.pop(list)?;
+        while list > wanted {
+            list -= 1;
+            self.push(list, block + block_size(list));
+        }
+        NonNull::new(block as *mut u8)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let mut list = list_for(layout).expect("allocated blocks fit a list");
+        let mut block = ptr.as_ptr() as usize;
+        while list + 1 < ORDERS {
+            let buddy = self.base + ((block - self.base) ^ block_size(list));
+            if !self.remove(list, buddy) {
+                break;
+            }
+            block = block.min(buddy);
+            list += 1;
+        }
+        self.push(list, block);
+    }
+
+    fn free_space(&self) -> (usize, usize) {
+        (0..ORDERS).fold((0, 0), |(free, largest), list| match self.blocks(list) {
+            0 => (free, largest),
+            count => (free + count * block_size(list), block_size(list)),
+        })
+    }
+}
@@ -0,0 +1,50 @@
+//! Hands out memory in order and only takes it back once everything has been freed. The
+//! fastest allocator, and the most wasteful.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use super::HeapAllocator;
+
+pub struct BumpAllocator {
+    start: usize,
+    end: usize,
+    /// Where the next allocation goes.
+    next: usize,
+    live: usize,
+}
+
+impl HeapAllocator for BumpAllocator {
+    const NAME: &'static str = "bump";
+    const EMPTY: Self = Self {
+        start: 0,
+        end: 0,
+        next: 0,
+        live: 0,
+    };
+
+    unsafe fn init(&mut self, start: *mut u8, size: usize) {
+        self.start = start as usize;
+        self.end = self.start + size;
+        self.next = self.start;
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let start = self.next.checked_next_multiple_of(layout.align())?;
+        let end = start.checked_add(layout.size()).filter(|&end| end <= self.end)?;
+        self.next = end;
+        self.live += 1;
+        NonNull::new(start as *mut u8)
+    }
+
+    unsafe fn deallocate(&mut self, _ptr: NonNull<u8>, _layout: Layout) {
+        self.live -= 1;
+        if self.live == 0 {
+            self.next = self.start;
+        }
+    }
+
+    fn free_space(&self) -> (usize, usize) {
+        (self.end - self.next, self.end - self.next)
+    }
+}
@@ -0,0 +1,140 @@
+//! First-fit allocation from a list of free blocks sorted by address. Freed blocks are merged
+//! with their neighbours, so memory is reused but can fragment.
+
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::{self, NonNull};
+
+use super::HeapAllocator;
+
+/// Header written into each free block.
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Blocks smaller than this cannot hold a [FreeBlock] once freed.
+const MIN_BLOCK: usize = size_of::<FreeBlock>();
+
+pub struct LinkedListAllocator {
+    /// The free block with the lowest address.
+    head: *mut FreeBlock,
+}
+
+// the allocator owns the memory its pointers point into
+unsafe impl Send for LinkedListAllocator {}
+
+impl LinkedListAllocator {
+    /// Size and alignment of the block that serves `layout`, which must be able to hold a
+    /// [FreeBlock] once it is freed.
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let align = layout.align().max(align_of::<FreeBlock>());
+        let size = layout.size().max(MIN_BLOCK).next_multiple_of(align_of::<FreeBlock>());
+        (size, align)
+    }
+
+    /// Start and end of a block of `size` bytes aligned to `align` placed at or after
+    /// `block_start`, or `None` if it would run past the end of the address space.
+    fn place(block_start: usize, size: usize, align: usize) -> Option<(usize, usize)> {
+        let mut start = block_start.checked_next_multiple_of(align)?;
+        // the space skipped for alignment stays free, so it must be able to hold a block
+        if start != block_start && start - block_start < MIN_BLOCK {
+            start = (block_start + MIN_BLOCK).checked_next_multiple_of(align)?;
+        }
+        Some((start, start.checked_add(size)?))
+    }
+
+    /// Adds the `size` bytes at `start` to the free blocks, merging them with adjacent ones.
+    ///
+    /// # Safety
+    /// The memory must be unused and belong to the heap.
+    pub unsafe fn insert(&mut self, start: usize, size: usize) {
+        let mut previous: *mut FreeBlock = ptr::null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < start {
+            previous = next;
+            next = (*next).next;
+        }
+        let block = start as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+        if previous.is_null() {
+            self.head = block;
+        } else {
+            (*previous).next = block;
+        }
+        if !next.is_null() && start + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+        if !previous.is_null() && previous as usize + (*previous).size == start {
+            (*previous).size += (*block).size;
+            (*previous).next = (*block).next;
+        }
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = &FreeBlock> {
+        let mut next = self.head;
+        core::iter::from_fn(move || {
+            // every block in the list is a valid FreeBlock
+            let block = unsafe { next.as_ref()? };
+            next = block.next;
+            Some(block)
+        })
+    }
+}
+
+impl HeapAllocator for LinkedListAllocator {
+    const NAME: &'static str = "linked list";
+    const EMPTY: Self = Self {
+        head: ptr::null_mut(),
+    };
+
+    unsafe fn init(&mut self, start: *mut u8, size: usize) {
+        self.insert(start as usize, size - size % align_of::<FreeBlock>());
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = Self::block_layout(layout);
+        let mut previous: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+        while !current.is_null() {
+            let (block_start, block_size, next) =
+                unsafe { (current as usize, (*current).size, (*current).next) };
+            let block_end = block_start + block_size;
+            let placement = Self::place(block_start, size, align);
+            let fits = placement.filter(|&(_, end)| {
+                end == block_end || end.checked_add(MIN_BLOCK).is_some_and(|end| end <= block_end)
+            });
+            if let Some((start, end)) = fits {
+                unsafe {
+                    if previous.is_null() {
+                        self.head = next;
+                    } else {
+                        (*previous).next = next;
+                    }
+                    if start > block_start {
+                        self.insert(block_start, start - block_start);
+                    }
+                    if end < block_end {
+                        self.insert(end, block_end - end);
+                    }
+                }
+                return NonNull::new(start as *mut u8);
+            }
+            previous = current;
+            current = next;
+        }
+        None
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+        self.insert(ptr.as_ptr() as usize, size);
+    }
+
+    fn free_space(&self) -> (usize, usize) {
+        self.blocks().fold((0, 0), |(free, largest), block| {
+            (free + block.size, largest.max(block.size))
+        })
+    }
+}
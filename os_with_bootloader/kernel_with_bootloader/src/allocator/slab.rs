@@ -0,0 +1,88 @@
+//! Fixed-size blocks: small allocations are rounded up to one of a few block sizes, and freed
+//! blocks are kept in a list per size for the next allocation of that size. Larger allocations,
+//! and new blocks, come from a linked list allocator.
+
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+use super::linked_list::LinkedListAllocator;
+use super::HeapAllocator;
+
+/// Sizes of the blocks, each also used as its alignment.
+const BLOCK_SIZES: [usize; 9] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+/// Header written into each free block.
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+pub struct SlabAllocator {
+    /// Free blocks of each of the [BLOCK_SIZES].
+    free_lists: [*mut FreeBlock; BLOCK_SIZES.len()],
+    /// Bytes in the free lists.
+    free_blocks: usize,
+    fallback: LinkedListAllocator,
+}
+
+// the allocator owns the memory its pointers point into
+unsafe impl Send for SlabAllocator {}
+
+/// Index of the smallest block size that fits `layout`, if any does.
+fn size_index(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    BLOCK_SIZES.iter().position(|&block_size| block_size >= size)
+}
+
+impl HeapAllocator for SlabAllocator {
+    const NAME: &'static str = "slab";
+    const EMPTY: Self = Self {
+        free_lists: [ptr::null_mut(); BLOCK_SIZES.len()],
+        free_blocks: 0,
+        fallback: LinkedListAllocator::EMPTY,
+    };
+
+    unsafe fn init(&mut self, start: *mut u8, size: usize) {
+        self.fallback.init(start, size);
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let Some(index) = size_index(layout) else {
+            return self.fallback.allocate(layout);
+        };
+        match NonNull::new(self.free_lists[index]) {
+            Some(block) => {
+                // blocks in the free lists are valid FreeBlocks
+                self.free_lists[index] = unsafe { block.as_ref().next };
+                self.free_blocks -= BLOCK_SIZES[index];
+                Some(block.cast())
+            }
+            None => {
+                let block_size = BLOCK_SIZES[index];
+                let layout = Layout::from_size_align(block_size, block_size).ok()?;
+                self.fallback.allocate(layout)
+            }
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let Some(index) = size_index(layout) else {
+            return self.fallback.deallocate(ptr, layout);
+        };
+        // blocks stay in their size's list rather than going back to the fallback
+        let block = ptr.cast::<FreeBlock>().as_ptr();
+        block.write(FreeBlock {
+            next: self.free_lists[index],
+        });
+        self.free_lists[index] = block;
+        self.free_blocks += BLOCK_SIZES[index];
+    }
+
+    fn free_space(&self) -> (usize, usize) {
+        let (free, largest) = self.fallback.free_space();
+        let largest_block = (0..BLOCK_SIZES.len())
+            .rev()
+            .find(|&index| !self.free_lists[index].is_null())
+            .map_or(0, |index| BLOCK_SIZES[index]);
+        (free + self.free_blocks, largest.max(largest_block))
+    }
+}
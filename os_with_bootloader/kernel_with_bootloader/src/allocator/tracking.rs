@@ -0,0 +1,108 @@
+//! Records where live heap allocations were made, to find leaks with. Enabled by `heap_debug`.
+
+use core::ptr::NonNull;
+
+use super::with_heap;
+use crate::backtrace::Frames;
+
+/// Freed memory is filled with this, so that use after free stands out.
+pub const FREED_POISON: u8 = 0xdd;
+
+/// Return addresses recorded per allocation, innermost first.
+pub const CALLERS: usize = 8;
+
+/// Most allocations recorded at the same time. Later ones are counted but not recorded, and
+/// the memory of unrecorded allocations is never reused, see [Tracker::forget].
+const MAX_RECORDS: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    pub address: usize,
+    pub size: usize,
+    /// Allocations made before this one since boot.
+    pub sequence: u64,
+    /// Return addresses of the calls that led to the allocation, 0 where the stack ended.
+    pub callers: [u64; CALLERS],
+}
+
+pub(super) struct Tracker {
+    records: [Option<Allocation>; MAX_RECORDS],
+    next_sequence: u64,
+    /// Allocations before this sequence number are not reported by [live_allocations].
+    mark: u64,
+    /// Live allocations there was no room to record.
+    unrecorded: usize,
+}
+
+impl Tracker {
+    pub const EMPTY: Self = Self {
+        records: [None; MAX_RECORDS],
+        next_sequence: 0,
+        mark: 0,
+        unrecorded: 0,
+    };
+
+    pub fn record(&mut self, ptr: NonNull<u8>, size: usize, callers: Frames) {
+        let mut addresses = [0; CALLERS];
+        for (slot, address) in addresses.iter_mut().zip(callers) {
+            *slot = address;
+        }
+        let allocation = Allocation {
+            address: ptr.as_ptr() as usize,
+            size,
+            sequence: self.next_sequence,
+            callers: addresses,
+        };
+        self.next_sequence += 1;
+        match self.records.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(allocation),
+            None => self.unrecorded += 1,
+        }
+    }
+
+    /// Drops the record of the allocation at `ptr`. Returns whether it was found: without a
+    /// record, `ptr` is taken to be one of the unrecorded allocations, but it may as well have
+    /// been freed before, so it must not be handed back to the allocator.
+    ///
+    /// # Panics
+    /// If there is no record of `ptr` and no unrecorded allocation it could be: it was freed
+    /// twice or never allocated.
+    pub fn forget(&mut self, ptr: NonNull<u8>) -> bool {
+        let address = ptr.as_ptr() as usize;
+        let record = self.records.iter_mut().find(|slot| {
+            slot.is_some_and(|allocation| allocation.address == address)
+        });
+        match record {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None if self.unrecorded > 0 => {
+                self.unrecorded -= 1;
+                false
+            }
+            None => panic!("freeing {:p}, which is not allocated", ptr),
+        }
+    }
+}
+
+/// Leaves everything allocated so far out of [live_allocations].
+pub fn mark() {
+    with_heap(|heap| heap.tracker.mark = heap.tracker.next_sequence);
+}
+
+/// Calls `f` for every live allocation made since the last [mark]. Returns how many live
+/// allocations were not recorded because the table was full.
+pub fn live_allocations(mut f: impl FnMut(Allocation)) -> usize {
+    for index in 0..MAX_RECORDS {
+        // unlocked while f runs, which may well allocate
+        let record = with_heap(|heap| {
+            let mark = heap.tracker.mark;
+            heap.tracker.records[index].filter(|allocation| allocation.sequence >= mark)
+        });
+        if let Some(allocation) = record {
+            f(allocation);
+        }
+    }
+    with_heap(|heap| heap.tracker.unrecorded)
+}
@@ -0,0 +1,209 @@
+use core::ptr;
+
+use spin::{Mutex, Once};
+use x86_64::registers::model_specific::Msr;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::acpi::{self, AcpiError, Madt};
+use crate::interrupts::{self, irq_vector};
+use crate::memory::phys_to_virt;
+use crate::time;
+
+/// Vector the local APIC uses for spurious interrupts. These must not be acknowledged.
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+// Local APIC register offsets
+const LAPIC_ID: usize = 0x020;
+const LAPIC_TASK_PRIORITY: usize = 0x080;
+const LAPIC_EOI: usize = 0x0B0;
+const LAPIC_SPURIOUS: usize = 0x0F0;
+const LAPIC_LVT_TIMER: usize = 0x320;
+const LAPIC_TIMER_INITIAL_COUNT: usize = 0x380;
+const LAPIC_TIMER_CURRENT_COUNT: usize = 0x390;
+const LAPIC_TIMER_DIVIDE: usize = 0x3E0;
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const APIC_BASE_GLOBAL_ENABLE: u64 = 1 << 11;
+const SPURIOUS_APIC_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+/// Divide configuration value for "divide by 16".
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+/// Timer ticks to measure the local APIC timer against when calibrating it.
+const CALIBRATION_TICKS: u32 = 10;
+
+// I/O APIC registers
+const IOAPIC_REGISTER_SELECT: usize = 0x00;
+const IOAPIC_WINDOW: usize = 0x10;
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+const MAX_IO_APICS: usize = 8;
+
+/// The local APIC of the CPU we run on, accessed through the physical memory mapping.
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    fn read(&self, register: usize) -> u32 {
+        unsafe { ptr::read_volatile((self.base + register).as_ptr()) }
+    }
+
+    fn write(&self, register: usize, value: u32) {
+        unsafe { ptr::write_volatile((self.base + register).as_mut_ptr(), value) }
+    }
+
+    pub fn id(&self) -> u8 {
+        (self.read(LAPIC_ID) >> 24) as u8
+    }
+
+    /// Signals the end of the interrupt currently being handled.
+    pub fn end_of_interrupt(&self) {
+        self.write(LAPIC_EOI, 0);
+    }
+
+    /// Counts how far the timer runs (divided by 16) during one tick of the current clock.
+    fn calibrate_timer(&self) -> u32 {
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_LVT_TIMER, LVT_MASKED);
+        // start right after a tick so the measurement covers whole ticks
+        time::sleep_ticks(1);
+        self.write(LAPIC_TIMER_INITIAL_COUNT, u32::MAX);
+        time::sleep_ticks(CALIBRATION_TICKS as u64);
+        let elapsed = u32::MAX - self.read(LAPIC_TIMER_CURRENT_COUNT);
+        self.write(LAPIC_TIMER_INITIAL_COUNT, 0);
+        elapsed / CALIBRATION_TICKS
+    }
+
+    fn start_periodic_timer(&self, vector: u8, count: u32) {
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_LVT_TIMER, LVT_TIMER_PERIODIC | vector as u32);
+        self.write(LAPIC_TIMER_INITIAL_COUNT, count);
+    }
+}
+
+/// An I/O APIC, which routes global system interrupts (GSIs) to local APICs.
+pub struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+    redirection_entries: u32,
+}
+
+impl IoApic {
+    fn read(&mut self, register: u32) -> u32 {
+        unsafe {
+            ptr::write_volatile((self.base + IOAPIC_REGISTER_SELECT).as_mut_ptr(), register);
+            ptr::read_volatile((self.base + IOAPIC_WINDOW).as_ptr())
+        }
+    }
+
+    fn write(&mut self, register: u32, value: u32) {
+        unsafe {
+            ptr::write_volatile((self.base + IOAPIC_REGISTER_SELECT).as_mut_ptr(), register);
+            ptr::write_volatile((self.base + IOAPIC_WINDOW).as_mut_ptr(), value);
+        }
+    }
+
+    fn handles(&self, gsi: u32) -> bool {
+        (self.gsi_base..self.gsi_base + self.redirection_entries).contains(&gsi)
+    }
+
+    fn set_redirection(&mut self, gsi: u32, entry: u64) {
+        let register = IOAPIC_REDIRECTION_TABLE + 2 * (gsi - self.gsi_base);
+        // mask first so the line never fires with a half-written entry
+        self.write(register, REDIRECTION_MASKED as u32);
+        self.write(register + 1, (entry >> 32) as u32);
+        self.write(register, entry as u32);
+    }
+}
+
+static LOCAL_APIC: Once<LocalApic> = Once::new();
+static IO_APICS: Mutex<[Option<IoApic>; MAX_IO_APICS]> =
+    Mutex::new([const { None }; MAX_IO_APICS]);
+static MADT: Once<&'static Madt> = Once::new();
+
+/// Switches interrupt handling from the 8259 PICs to the APICs described by the ACPI MADT.
+///
+/// Requires the tick clock to be running with interrupts enabled, because the local APIC timer
+/// is calibrated against it before it takes over as the tick source.
+pub fn init() -> Result<(), AcpiError> {
+    let madt = acpi::tables()
+        .and_then(|tables| tables.madt.as_ref())
+        .ok_or(AcpiError::TableNotFound(*b"APIC"))?;
+    MADT.call_once(|| madt);
+
+    let mut apic_base = Msr::new(IA32_APIC_BASE);
+    unsafe { apic_base.write(apic_base.read() | APIC_BASE_GLOBAL_ENABLE) };
+    let local_apic = LOCAL_APIC.call_once(|| LocalApic {
+        base: phys_to_virt(PhysAddr::new(madt.local_apic_address)),
+    });
+    local_apic.write(LAPIC_TASK_PRIORITY, 0);
+    local_apic.write(LAPIC_SPURIOUS, SPURIOUS_APIC_ENABLE | SPURIOUS_VECTOR as u32);
+
+    {
+        let mut io_apics = IO_APICS.lock();
+        for (slot, info) in io_apics.iter_mut().zip(madt.io_apics()) {
+            log::debug!("I/O APIC {} at {:#x}, GSI base {}", info.id, info.address, info.gsi_base);
+            let mut io_apic = IoApic {
+                base: phys_to_virt(PhysAddr::new(info.address as u64)),
+                gsi_base: info.gsi_base,
+                redirection_entries: 0,
+            };
+            io_apic.redirection_entries = ((io_apic.read(IOAPIC_VERSION) >> 16) & 0xFF) + 1;
+            for gsi in io_apic.gsi_base..io_apic.gsi_base + io_apic.redirection_entries {
+                io_apic.set_redirection(gsi, REDIRECTION_MASKED);
+            }
+            *slot = Some(io_apic);
+        }
+    }
+
+    let count_per_tick = local_apic.calibrate_timer();
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        interrupts::switch_to_apic(madt.has_legacy_pics);
+        // the local APIC timer replaces the PIT on the same vector, so ticks keep counting
+        local_apic.start_periodic_timer(irq_vector(time::TIMER_IRQ), count_per_tick);
+    });
+    log::info!(
+        "APIC enabled: {} CPU(s), {} I/O APIC(s), timer at {} counts per tick",
+        madt.processor_count,
+        madt.io_apics().count(),
+        count_per_tick
+    );
+    Ok(())
+}
+
+/// Acknowledges the interrupt being handled on this CPU.
+pub fn end_of_interrupt() {
+    if let Some(local_apic) = LOCAL_APIC.get() {
+        local_apic.end_of_interrupt();
+    }
+}
+
+/// Routes ISA IRQ line `irq` through the I/O APIC to vector `vector` on this CPU, honoring the
+/// MADT's interrupt source overrides.
+pub fn route_irq(irq: u8, vector: u8) {
+    let (Some(madt), Some(local_apic)) = (MADT.get(), LOCAL_APIC.get()) else { return };
+    let mut entry = vector as u64 | (local_apic.id() as u64) << 56;
+    let gsi = match madt.interrupt_override(irq) {
+        Some(source_override) => {
+            if source_override.active_low() {
+                entry |= REDIRECTION_ACTIVE_LOW;
+            }
+            if source_override.level_triggered() {
+                entry |= REDIRECTION_LEVEL_TRIGGERED;
+            }
+            source_override.gsi
+        }
+        None => irq as u32,
+    };
+    let mut io_apics = IO_APICS.lock();
+    match io_apics.iter_mut().flatten().find(|io_apic| io_apic.handles(gsi)) {
+        Some(io_apic) => io_apic.set_redirection(gsi, entry),
+        None => log::warn!("no I/O APIC handles GSI {} (IRQ {})", gsi, irq),
+    }
+}
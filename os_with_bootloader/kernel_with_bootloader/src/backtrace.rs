@@ -0,0 +1,250 @@
+//! Stack backtraces, found by following the chain of saved frame pointers (the kernel is built
+//! with `-C force-frame-pointers=yes`) and named with the symbol table that the os_with_bootloader
+//! build script writes into the kernel image.
+//!
+//! Functions built without frame pointers, such as those of the precompiled `core` library, do
+//! not show up in the chain.
+
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::ptr;
+
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::VirtAddr;
+
+use crate::memory::paging;
+
+/// Room for the symbol table. Symbols that do not fit are left out by the build script.
+const SYMBOL_TABLE_SIZE: usize = 512 * 1024;
+
+/// Start of [SYMBOL_TABLE] as built, which the build script checks before filling it in.
+const EMPTY_MAGIC: [u8; 8] = *b"KSYMNONE";
+/// Start of [SYMBOL_TABLE] once the build script has filled it in.
+const MAGIC: [u8; 8] = *b"KSYMTAB1";
+
+/// Size of the table header: [MAGIC], the address the table was linked at, the number of
+/// symbols and four bytes of padding.
+const HEADER_SIZE: usize = 24;
+/// Size of a symbol entry: address, size, and offset and length of the name in the names that
+/// follow the entries. Entries are sorted by address.
+const ENTRY_SIZE: usize = 20;
+
+/// Most frames a backtrace shows, in case the frame pointer chain loops.
+const MAX_FRAMES: usize = 64;
+
+/// Filled in by the build script after the kernel is linked, which finds it by its section
+/// name. Never written at run time; it is `static mut` so that the compiler cannot assume it
+/// still holds [EMPTY_MAGIC].
+#[used]
+#[link_section = ".kernel_symbols"]
+static mut SYMBOL_TABLE: [u8; SYMBOL_TABLE_SIZE] = {
+    let mut table = [0; SYMBOL_TABLE_SIZE];
+    let mut index = 0;
+    while index < EMPTY_MAGIC.len() {
+        table[index] = EMPTY_MAGIC[index];
+        index += 1;
+    }
+    table
+};
+
+/// The symbol table the build script embedded, if it did.
+#[derive(Clone, Copy)]
+struct SymbolTable {
+    entries: &'static [u8],
+    names: &'static [u8],
+    /// What to subtract from a run-time address to get the link-time address it is listed at.
+    /// The bootloader may load the kernel elsewhere than it was linked for.
+    load_offset: u64,
+}
+
+impl SymbolTable {
+    fn get() -> Option<Self> {
+        // SYMBOL_TABLE is only ever read
+        let table: &'static [u8] = unsafe { &*ptr::addr_of!(SYMBOL_TABLE) };
+        if table[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let link_address = read_u64(table, 8);
+        let count = read_u32(table, 16) as usize;
+        let (entries, names) = table[HEADER_SIZE..].split_at_checked(count * ENTRY_SIZE)?;
+        Some(Self {
+            entries,
+            names,
+            load_offset: (table.as_ptr() as u64).wrapping_sub(link_address),
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len() / ENTRY_SIZE
+    }
+
+    fn address(&self, index: usize) -> u64 {
+        read_u64(self.entries, index * ENTRY_SIZE)
+    }
+
+    /// Returns the function containing `address` and how far into it `address` is.
+    fn resolve(&self, address: u64) -> Option<(&'static str, u64)> {
+        let address = address.wrapping_sub(self.load_offset);
+        // index of the first symbol starting after address
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.address(middle) <= address {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let entry = low.checked_sub(1)? * ENTRY_SIZE;
+        let start = read_u64(self.entries, entry);
+        let size = read_u32(self.entries, entry + 8) as u64;
+        if address >= start + size {
+            return None;
+        }
+        let name_start = read_u32(self.entries, entry + 12) as usize;
+        let name_length = read_u32(self.entries, entry + 16) as usize;
+        let name = self.names.get(name_start..name_start + name_length)?;
+        Some((core::str::from_utf8(name).unwrap_or("?"), address - start))
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+/// Iterates over the return addresses in a chain of stack frames, innermost first.
+#[derive(Clone, Copy)]
+pub struct Frames {
+    frame_pointer: u64,
+    depth: usize,
+}
+
+impl Frames {
+    /// The frames of the caller and everything that called it.
+    #[inline(always)]
+    pub fn here() -> Self {
+        let frame_pointer: u64;
+        unsafe { asm!("mov {}, rbp", out(reg) frame_pointer, options(nomem, nostack)) };
+        Self {
+            frame_pointer,
+            depth: 0,
+        }
+    }
+
+    /// The frames of the code that was running when the exception with `stack_frame` was
+    /// raised, if the handler's frame can be found from here.
+    #[inline(always)]
+    pub fn interrupted(stack_frame: &InterruptStackFrame) -> Option<Self> {
+        let instruction_pointer = stack_frame.instruction_pointer.as_u64();
+        let mut frames = Self::here();
+        while frames.depth < MAX_FRAMES {
+            let frame = frames.frame_pointer;
+            // A handler's frame sits right below what the CPU pushed: the interrupted
+            // instruction pointer, possibly preceded by an error code.
+            if !readable(frame, 3) {
+                return None;
+            }
+            let slots = frame as *const u64;
+            let (return_slot, error_code_slot) = unsafe { (*slots.add(1), *slots.add(2)) };
+            if return_slot == instruction_pointer || error_code_slot == instruction_pointer {
+                return Some(Self {
+                    frame_pointer: unsafe { *slots },
+                    depth: 0,
+                });
+            }
+            frames.next()?;
+        }
+        None
+    }
+}
+
+impl Iterator for Frames {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let frame = self.frame_pointer;
+        if self.depth == MAX_FRAMES || !readable(frame, 2) {
+            return None;
+        }
+        let slots = frame as *const u64;
+        let (caller_frame, return_address) = unsafe { (*slots, *slots.add(1)) };
+        // the stack grows down, so a caller's frame is always above its callee's
+        self.frame_pointer = if caller_frame > frame { caller_frame } else { 0 };
+        self.depth += 1;
+        (return_address != 0).then_some(return_address)
+    }
+}
+
+/// Whether the `count` words at `frame` can be read without faulting.
+fn readable(frame: u64, count: u64) -> bool {
+    let last = frame.wrapping_add((count - 1) * 8);
+    frame != 0
+        && frame.is_multiple_of(8)
+        && last > frame
+        && VirtAddr::try_new(last).is_ok()
+        && paging::is_mapped(VirtAddr::new(frame))
+        && paging::is_mapped(VirtAddr::new(last))
+}
+
+/// Writes one line per frame, `function+offset` where the symbol table knows the address.
+pub fn write(out: &mut impl Write, frames: Frames) -> fmt::Result {
+    write_from(out, 0, frames)
+}
+
+/// Like [write], but starts with the instruction the exception with `stack_frame` interrupted.
+pub fn write_interrupted(out: &mut impl Write, stack_frame: &InterruptStackFrame) -> fmt::Result {
+    let instruction_pointer = stack_frame.instruction_pointer.as_u64();
+    let symbol = SymbolTable::get().and_then(|table| table.resolve(instruction_pointer));
+    write_frame(out, 0, instruction_pointer, symbol)?;
+    match Frames::interrupted(stack_frame) {
+        Some(frames) => write_from(out, 1, frames),
+        None => writeln!(out, "  (cannot find the frame the exception interrupted)"),
+    }
+}
+
+fn write_from(out: &mut impl Write, first_index: usize, frames: Frames) -> fmt::Result {
+    let table = SymbolTable::get();
+    if table.is_none() {
+        writeln!(out, "  (no symbol table was embedded, showing addresses only)")?;
+    }
+    for (index, return_address) in (first_index..).zip(frames) {
+        let symbol = resolve_return_address(return_address);
+        write_frame(out, index, return_address, symbol)?;
+        // outside the kernel, most likely the bootloader that called the entry point
+        if table.is_some() && symbol.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the function a call returns to `return_address` in, and the offset of
+/// `return_address` from its start.
+pub fn resolve_return_address(return_address: u64) -> Option<(&'static str, u64)> {
+    // the call is the instruction before the one returned to, which may be in the next
+    // function if the call was the last instruction
+    let (name, offset) = SymbolTable::get()?.resolve(return_address - 1)?;
+    Some((name, offset + 1))
+}
+
+fn write_frame(
+    out: &mut impl Write,
+    index: usize,
+    address: u64,
+    symbol: Option<(&str, u64)>,
+) -> fmt::Result {
+    match symbol {
+        Some((name, offset)) => {
+            writeln!(out, "{:>3}: {:#018x} {}+{:#x}", index, address, name, offset)
+        }
+        None => writeln!(out, "{:>3}: {:#018x} ??", index, address),
+    }
+}
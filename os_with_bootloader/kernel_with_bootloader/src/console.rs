@@ -0,0 +1,43 @@
+use core::fmt;
+
+use crate::registry::Registry;
+
+/// An output that receives a copy of everything written to the framebuffer console.
+pub type Sink = fn(&str);
+
+/// Maximum number of sinks that can be registered at the same time.
+const MAX_SINKS: usize = 4;
+
+static SINKS: Registry<Sink, MAX_SINKS> = Registry::new();
+
+/// Adds `sink` to the outputs that mirror the console, unless [MAX_SINKS] are there already.
+pub fn register_sink(sink: Sink) -> Result<(), Sink> {
+    SINKS.register(sink)
+}
+
+/// Passes `s` on to every registered sink. Called by [crate::writer::FrameBufferWriter] for each
+/// string it renders.
+pub fn mirror(s: &str) {
+    for sink in SINKS.items() {
+        sink(s);
+    }
+}
+
+/// Releases the sink table if the code that panicked was holding it.
+///
+/// # Safety
+/// Same as [crate::serial::force_unlock].
+pub unsafe fn force_unlock() {
+    SINKS.force_unlock();
+}
+
+/// Writes straight to the registered sinks, bypassing the framebuffer. Used for output produced
+/// while the framebuffer console is not available.
+pub struct SinkWriter;
+
+impl fmt::Write for SinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        mirror(s);
+        Ok(())
+    }
+}
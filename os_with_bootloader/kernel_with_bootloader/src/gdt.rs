@@ -0,0 +1,83 @@
+use core::ptr::addr_of;
+
+use spin::Lazy;
+use x86_64::instructions::segmentation::{Segment, CS, DS, ES, SS};
+use x86_64::instructions::tables::load_tss;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+/// Interrupt Stack Table slot used by the double fault handler.
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+/// Interrupt Stack Table slot used by the NMI handler, which can arrive at any point.
+pub const NMI_IST_INDEX: u16 = 1;
+/// Interrupt Stack Table slot used by the machine check handler.
+pub const MACHINE_CHECK_IST_INDEX: u16 = 2;
+
+/// Size of each dedicated exception stack.
+const IST_STACK_SIZE: usize = 5 * 4096;
+/// Size of the stack the CPU switches to when an interrupt arrives in user mode.
+const PRIVILEGE_STACK_SIZE: usize = 5 * 4096;
+
+#[repr(align(16))]
+struct Stack<const SIZE: usize>([u8; SIZE]);
+
+static mut DOUBLE_FAULT_STACK: Stack<IST_STACK_SIZE> = Stack([0; IST_STACK_SIZE]);
+static mut NMI_STACK: Stack<IST_STACK_SIZE> = Stack([0; IST_STACK_SIZE]);
+static mut MACHINE_CHECK_STACK: Stack<IST_STACK_SIZE> = Stack([0; IST_STACK_SIZE]);
+static mut PRIVILEGE_STACK: Stack<PRIVILEGE_STACK_SIZE> = Stack([0; PRIVILEGE_STACK_SIZE]);
+
+/// Returns the top of `stack`; x86_64 stacks grow downwards.
+fn stack_top<const SIZE: usize>(stack: *const Stack<SIZE>) -> VirtAddr {
+    VirtAddr::from_ptr(stack) + SIZE as u64
+}
+
+static TSS: Lazy<TaskStateSegment> = Lazy::new(|| {
+    let mut tss = TaskStateSegment::new();
+    // The stacks are only ever handed to the CPU, never accessed from Rust.
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+        stack_top(addr_of!(DOUBLE_FAULT_STACK));
+    tss.interrupt_stack_table[NMI_IST_INDEX as usize] = stack_top(addr_of!(NMI_STACK));
+    tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] =
+        stack_top(addr_of!(MACHINE_CHECK_STACK));
+    tss.privilege_stack_table[0] = stack_top(addr_of!(PRIVILEGE_STACK));
+    tss
+});
+
+/// Segment selectors of the kernel-owned GDT.
+struct Selectors {
+    kernel_code: SegmentSelector,
+    kernel_data: SegmentSelector,
+    tss: SegmentSelector,
+}
+
+static GDT: Lazy<(GlobalDescriptorTable, Selectors)> = Lazy::new(|| {
+    let mut gdt = GlobalDescriptorTable::new();
+    let kernel_code = gdt.add_entry(Descriptor::kernel_code_segment());
+    let kernel_data = gdt.add_entry(Descriptor::kernel_data_segment());
+    // nothing runs in ring 3 yet, but user data has to come before user code for `sysret`
+    gdt.add_entry(Descriptor::user_data_segment());
+    gdt.add_entry(Descriptor::user_code_segment());
+    let tss = gdt.add_entry(Descriptor::tss_segment(&TSS));
+    (
+        gdt,
+        Selectors {
+            kernel_code,
+            kernel_data,
+            tss,
+        },
+    )
+});
+
+/// Replaces the bootloader's GDT with ours and loads the TSS.
+pub fn init() {
+    let (gdt, selectors) = &*GDT;
+    gdt.load();
+    unsafe {
+        CS::set_reg(selectors.kernel_code);
+        DS::set_reg(selectors.kernel_data);
+        ES::set_reg(selectors.kernel_data);
+        SS::set_reg(selectors.kernel_data);
+        load_tss(selectors.tss);
+    }
+}
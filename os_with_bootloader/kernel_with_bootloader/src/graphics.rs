@@ -0,0 +1,419 @@
+mod color;
+
+pub use color::Color;
+
+use crate::screen::{Rect, Screen};
+
+/// Most corners [Canvas::fill_polygon] takes into account; further ones are ignored.
+pub const MAX_POLYGON_POINTS: usize = 32;
+
+/// A position on the screen in pixels. Shapes may extend past the edges, so coordinates are
+/// signed; whatever lies outside the screen is clipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Point {
+    pub const fn new(x: isize, y: isize) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A rectangular image that can be drawn with [Canvas::blit].
+pub trait Bitmap {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    /// The color of pixel `x`, `y`, or `None` where the image is transparent.
+    fn pixel(&self, x: usize, y: usize) -> Option<Color>;
+}
+
+/// Another bitmap resized with nearest-neighbour sampling.
+pub struct Scaled<'a, B: Bitmap> {
+    source: &'a B,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, B: Bitmap> Scaled<'a, B> {
+    pub fn new(source: &'a B, width: usize, height: usize) -> Self {
+        Self {
+            source,
+            width,
+            height,
+        }
+    }
+
+    /// Scales `source` to the largest size that fits into `width` × `height` while keeping its
+    /// aspect ratio.
+    pub fn fit(source: &'a B, width: usize, height: usize) -> Self {
+        let (source_width, source_height) = (source.width().max(1), source.height().max(1));
+        // compare width / source_width with height / source_height without dividing
+        if width * source_height <= height * source_width {
+            Self::new(source, width, source_height * width / source_width)
+        } else {
+            Self::new(source, source_width * height / source_height, height)
+        }
+    }
+}
+
+impl<B: Bitmap> Bitmap for Scaled<'_, B> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        let source_x = x * self.source.width() / self.width;
+        let source_y = y * self.source.height() / self.height;
+        self.source.pixel(source_x, source_y)
+    }
+}
+
+/// Draws onto a [Screen], clipping everything to its bounds and reporting what changed to
+/// [Screen::mark_dirty].
+pub struct Canvas<'a> {
+    screen: &'a mut Screen,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(screen: &'a mut Screen) -> Self {
+        Self { screen }
+    }
+
+    pub fn width(&self) -> usize {
+        self.screen.info().width
+    }
+
+    pub fn height(&self) -> usize {
+        self.screen.info().height
+    }
+
+    pub fn plot(&mut self, point: Point, color: Color) {
+        self.fill_rect(point, 1, 1, color);
+    }
+
+    /// Draws a line from `from` to `to`, both ends included, with Bresenham's algorithm. Only
+    /// the part on the screen is stepped through.
+    pub fn line(&mut self, from: Point, to: Point, color: Color) {
+        let Some((from, to)) = self.clip_line(from, to) else { return };
+        let pixel = self.encode(color);
+        let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+        let (step_x, step_y) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+        let (mut x, mut y) = (from.x, from.y);
+        let mut error = dx + dy;
+        loop {
+            self.put(x, y, &pixel);
+            if x == to.x && y == to.y {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        self.mark_span(from.x.min(to.x), from.y.min(to.y), to.x.max(from.x), to.y.max(from.y));
+    }
+
+    /// Fills the `width` × `height` rectangle whose top left corner is `corner`.
+    pub fn fill_rect(&mut self, corner: Point, width: usize, height: usize, color: Color) {
+        let Some(rect) = self.clip(corner, width, height) else { return };
+        let pixel = self.encode(color);
+        let info = self.screen.info();
+        let bytes_per_pixel = info.bytes_per_pixel;
+        let buffer = self.screen.buffer_mut();
+        for y in rect.y..rect.y + rect.height {
+            let start = (y * info.stride + rect.x) * bytes_per_pixel;
+            let end = start + rect.width * bytes_per_pixel;
+            for bytes in buffer[start..end].chunks_exact_mut(bytes_per_pixel) {
+                bytes.copy_from_slice(&pixel[..bytes_per_pixel]);
+            }
+        }
+        self.screen.mark_dirty(rect);
+    }
+
+    /// Draws the one pixel wide outline of the `width` × `height` rectangle at `corner`.
+    pub fn rect(&mut self, corner: Point, width: usize, height: usize, color: Color) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let right = corner.x.saturating_add_unsigned(width - 1);
+        let bottom = corner.y.saturating_add_unsigned(height - 1);
+        self.fill_rect(corner, width, 1, color);
+        self.fill_rect(Point::new(corner.x, bottom), width, 1, color);
+        self.fill_rect(corner, 1, height, color);
+        self.fill_rect(Point::new(right, corner.y), 1, height, color);
+    }
+
+    /// Draws the outline of a circle, row by row like [Canvas::fill_circle].
+    pub fn circle(&mut self, center: Point, radius: usize, color: Color) {
+        let pixel = self.encode(color);
+        let radius = radius.min(isize::MAX as usize);
+        for (y, outer) in self.circle_rows(center, radius) {
+            // the outline reaches in to where the row further out ends, so that it has no gaps
+            // where it runs steeply
+            let dy = y.abs_diff(center.y);
+            let inner = match dy < radius {
+                true => (half_width(radius, dy + 1) + 1).min(outer),
+                false => 0,
+            };
+            let (left, right) = (center.x.saturating_sub(outer), center.x.saturating_add(outer));
+            self.span(left, center.x.saturating_sub(inner), y, &pixel);
+            self.span(center.x.saturating_add(inner), right, y, &pixel);
+        }
+        self.mark_around(center, radius);
+    }
+
+    /// Fills a circle, one horizontal span per row.
+    pub fn fill_circle(&mut self, center: Point, radius: usize, color: Color) {
+        let pixel = self.encode(color);
+        let radius = radius.min(isize::MAX as usize);
+        for (y, dx) in self.circle_rows(center, radius) {
+            self.span(center.x.saturating_sub(dx), center.x.saturating_add(dx), y, &pixel);
+        }
+        self.mark_around(center, radius);
+    }
+
+    /// The rows of the screen a circle covers, each with how far the circle reaches to either
+    /// side of its center there. `radius` must be at most `isize::MAX`.
+    fn circle_rows(&self, center: Point, radius: usize) -> impl Iterator<Item = (isize, isize)> {
+        let top = center.y.saturating_sub_unsigned(radius).max(0);
+        let bottom = center.y.saturating_add_unsigned(radius).min(self.height() as isize - 1);
+        (top..=bottom).map(move |y| (y, half_width(radius, y.abs_diff(center.y))))
+    }
+
+    pub fn triangle(&mut self, corners: [Point; 3], color: Color) {
+        self.line(corners[0], corners[1], color);
+        self.line(corners[1], corners[2], color);
+        self.line(corners[2], corners[0], color);
+    }
+
+    pub fn fill_triangle(&mut self, corners: [Point; 3], color: Color) {
+        self.fill_polygon(&corners, color);
+    }
+
+    /// Fills the polygon with the given corners using the even-odd rule, so self-intersecting
+    /// polygons get holes where they overlap. At most [MAX_POLYGON_POINTS] corners are used.
+    pub fn fill_polygon(&mut self, corners: &[Point], color: Color) {
+        let corners = &corners[..corners.len().min(MAX_POLYGON_POINTS)];
+        let (Some(top), Some(bottom)) = (
+            corners.iter().map(|point| point.y).min(),
+            corners.iter().map(|point| point.y).max(),
+        ) else {
+            return;
+        };
+        let pixel = self.encode(color);
+        let top = top.max(0);
+        let bottom = bottom.min(self.height() as isize - 1);
+        for y in top..=bottom {
+            // where the edges cross this row
+            let mut crossings = [0; MAX_POLYGON_POINTS];
+            let mut count = 0;
+            for (i, from) in corners.iter().enumerate() {
+                let to = corners[(i + 1) % corners.len()];
+                if (from.y <= y) != (to.y <= y) {
+                    let (dx, dy) = (to.x as i128 - from.x as i128, to.y as i128 - from.y as i128);
+                    // between from.x and to.x, since y is between their rows
+                    let offset = mul_div(dx, y as i128 - from.y as i128, dy);
+                    crossings[count] = (from.x as i128 + offset) as isize;
+                    count += 1;
+                }
+            }
+            let crossings = &mut crossings[..count];
+            crossings.sort_unstable();
+            for pair in crossings.chunks_exact(2) {
+                self.span(pair[0], pair[1], y, &pixel);
+            }
+        }
+        // the outline is part of the shape, also where a row only touches a corner
+        for (i, from) in corners.iter().enumerate() {
+            self.line(*from, corners[(i + 1) % corners.len()], color);
+        }
+        let left = corners.iter().map(|point| point.x).min().unwrap_or(0);
+        let right = corners.iter().map(|point| point.x).max().unwrap_or(0);
+        self.mark_span(left, top, right, bottom);
+    }
+
+    /// Draws `bitmap` with its top left corner at `corner`, leaving transparent pixels alone.
+    pub fn blit(&mut self, corner: Point, bitmap: &impl Bitmap) {
+        let Some(rect) = self.clip(corner, bitmap.width(), bitmap.height()) else { return };
+        let format = self.screen.info().pixel_format;
+        // where the visible part starts within the bitmap
+        let (skip_x, skip_y) = (rect.x as isize - corner.x, rect.y as isize - corner.y);
+        for y in 0..rect.height {
+            for x in 0..rect.width {
+                let source = (skip_x as usize + x, skip_y as usize + y);
+                if let Some(color) = bitmap.pixel(source.0, source.1) {
+                    let pixel = color.to_pixel(format);
+                    self.put((rect.x + x) as isize, (rect.y + y) as isize, &pixel);
+                }
+            }
+        }
+        self.screen.mark_dirty(rect);
+    }
+
+    /// Inverts every bit of the pixels in the rectangle, so that doing it twice restores them.
+    pub fn invert_rect(&mut self, corner: Point, width: usize, height: usize) {
+        let Some(rect) = self.clip(corner, width, height) else { return };
+        let info = self.screen.info();
+        let buffer = self.screen.buffer_mut();
+        for y in rect.y..rect.y + rect.height {
+            let start = (y * info.stride + rect.x) * info.bytes_per_pixel;
+            let end = start + rect.width * info.bytes_per_pixel;
+            for byte in &mut buffer[start..end] {
+                *byte = !*byte;
+            }
+        }
+        self.screen.mark_dirty(rect);
+    }
+
+    /// Moves the whole screen up by `rows` pixel rows. The rows that come into view at the
+    /// bottom keep their old contents until drawn over.
+    pub fn scroll_up(&mut self, rows: usize) {
+        let info = self.screen.info();
+        let buffer = self.screen.buffer_mut();
+        let shift = (rows * info.stride * info.bytes_per_pixel).min(buffer.len());
+        buffer.copy_within(shift.., 0);
+        self.screen.mark_all_dirty();
+    }
+
+    fn encode(&self, color: Color) -> [u8; 4] {
+        color.to_pixel(self.screen.info().pixel_format)
+    }
+
+    /// Writes one encoded pixel if it is on the screen. The caller marks the area as dirty.
+    fn put(&mut self, x: isize, y: isize, pixel: &[u8; 4]) {
+        if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.height() {
+            return;
+        }
+        let info = self.screen.info();
+        let offset = (y as usize * info.stride + x as usize) * info.bytes_per_pixel;
+        let bytes_per_pixel = info.bytes_per_pixel;
+        self.screen.buffer_mut()[offset..offset + bytes_per_pixel]
+            .copy_from_slice(&pixel[..bytes_per_pixel]);
+    }
+
+    /// Writes the pixels of row `y` from `left` to `right`, both included, that are on screen.
+    /// The caller marks the area as dirty.
+    fn span(&mut self, left: isize, right: isize, y: isize, pixel: &[u8; 4]) {
+        if y < 0 || y as usize >= self.height() {
+            return;
+        }
+        let (left, right) = (left.max(0), right.min(self.width() as isize - 1));
+        for x in left..=right {
+            self.put(x, y, pixel);
+        }
+    }
+
+    /// The part of the `width` × `height` rectangle at `corner` that is on the screen.
+    fn clip(&self, corner: Point, width: usize, height: usize) -> Option<Rect> {
+        let clip_axis = |start: isize, length: usize, limit: usize| {
+            let end = start.saturating_add_unsigned(length).clamp(0, limit as isize) as usize;
+            let start = start.clamp(0, limit as isize) as usize;
+            (start < end).then_some((start, end - start))
+        };
+        let (x, width) = clip_axis(corner.x, width, self.width())?;
+        let (y, height) = clip_axis(corner.y, height, self.height())?;
+        Some(Rect::new(x, y, width, height))
+    }
+
+    /// Marks the area from `left`, `top` to `right`, `bottom` (all included) as dirty.
+    fn mark_span(&mut self, left: isize, top: isize, right: isize, bottom: isize) {
+        let (left, top) = (left.max(0), top.max(0));
+        let right = right.min(self.width() as isize - 1);
+        let bottom = bottom.min(self.height() as isize - 1);
+        if left <= right && top <= bottom {
+            let (width, height) = ((right - left + 1) as usize, (bottom - top + 1) as usize);
+            self.screen.mark_dirty(Rect::new(left as usize, top as usize, width, height));
+        }
+    }
+
+    /// Marks the square around a circle as dirty.
+    fn mark_around(&mut self, center: Point, radius: usize) {
+        self.mark_span(
+            center.x.saturating_sub_unsigned(radius),
+            center.y.saturating_sub_unsigned(radius),
+            center.x.saturating_add_unsigned(radius),
+            center.y.saturating_add_unsigned(radius),
+        );
+    }
+
+    /// The part of the line from `from` to `to` that is on the screen, found with the
+    /// Cohen–Sutherland algorithm: an end beyond an edge is moved along the line onto it until
+    /// both ends are on the screen, or both beyond the same edge.
+    fn clip_line(&self, from: Point, to: Point) -> Option<(Point, Point)> {
+        const LEFT: u8 = 1 << 0;
+        const RIGHT: u8 = 1 << 1;
+        const ABOVE: u8 = 1 << 2;
+        const BELOW: u8 = 1 << 3;
+        let (right, bottom) = (self.width() as i128 - 1, self.height() as i128 - 1);
+        let outcode = |x: i128, y: i128| {
+            let mut code = 0;
+            if x < 0 {
+                code |= LEFT;
+            } else if x > right {
+                code |= RIGHT;
+            }
+            if y < 0 {
+                code |= ABOVE;
+            } else if y > bottom {
+                code |= BELOW;
+            }
+            code
+        };
+        let (mut x0, mut y0) = (from.x as i128, from.y as i128);
+        let (mut x1, mut y1) = (to.x as i128, to.y as i128);
+        loop {
+            let (code0, code1) = (outcode(x0, y0), outcode(x1, y1));
+            if code0 | code1 == 0 {
+                let point = |x: i128, y: i128| Point::new(x as isize, y as isize);
+                return Some((point(x0, y0), point(x1, y1)));
+            }
+            if code0 & code1 != 0 {
+                return None;
+            }
+            let code = if code0 != 0 { code0 } else { code1 };
+            let (x, y) = if code & ABOVE != 0 {
+                (x0 + mul_div(x1 - x0, -y0, y1 - y0), 0)
+            } else if code & BELOW != 0 {
+                (x0 + mul_div(x1 - x0, bottom - y0, y1 - y0), bottom)
+            } else if code & LEFT != 0 {
+                (0, y0 + mul_div(y1 - y0, -x0, x1 - x0))
+            } else {
+                (right, y0 + mul_div(y1 - y0, right - x0, x1 - x0))
+            };
+            if code == code0 {
+                (x0, y0) = (x, y);
+            } else {
+                (x1, y1) = (x, y);
+            }
+        }
+    }
+}
+
+/// `a * b / c`, rounded towards zero, for differences of two `isize`s, whose magnitudes are
+/// below 2⁶⁴ so that the product fits in a `u128`.
+fn mul_div(a: i128, b: i128, c: i128) -> i128 {
+    let magnitude = (a.unsigned_abs() * b.unsigned_abs() / c.unsigned_abs()) as i128;
+    if (a < 0) != ((b < 0) != (c < 0)) {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// How far a circle of `radius` reaches to either side of its center `dy` rows above or below
+/// it: the largest `dx` with `dx² + dy² <= radius²`. `dy` must not exceed `radius`.
+fn half_width(radius: usize, dy: usize) -> isize {
+    let (radius, dy) = (radius as u128, dy as u128);
+    (radius * radius - dy * dy).isqrt() as isize
+}
@@ -0,0 +1,110 @@
+use bootloader_api::info::PixelFormat;
+
+/// A 24-bit RGB color used for drawing on the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub const BLACK: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+    pub const GRAY: Color = Color::new(128, 128, 128);
+    pub const RED: Color = Color::new(255, 85, 85);
+    pub const GREEN: Color = Color::new(85, 255, 85);
+    pub const YELLOW: Color = Color::new(255, 255, 85);
+    pub const CYAN: Color = Color::new(85, 255, 255);
+    /// The yellowish tint the console has always used for text.
+    pub const DEFAULT_FOREGROUND: Color = Color::new(255, 255, 127);
+    pub const DEFAULT_BACKGROUND: Color = Color::BLACK;
+
+    /// Color `index` of the 256-color palette used by ANSI escape sequences: the 16 standard
+    /// colors, a 6×6×6 color cube and a 24-step gray ramp.
+    pub fn from_ansi_256(index: u8) -> Self {
+        match index {
+            0..=15 => ANSI_16[index as usize],
+            16..=231 => {
+                let level = |step: u8| if step == 0 { 0 } else { 55 + step * 40 };
+                let cube = index - 16;
+                Self::new(level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+            }
+            232..=255 => {
+                let gray = 8 + (index - 232) * 10;
+                Self::new(gray, gray, gray)
+            }
+        }
+    }
+
+    /// Scales every channel by `intensity / 255`, as used for anti-aliased glyph pixels.
+    pub fn scaled(self, intensity: u8) -> Self {
+        let scale = |channel: u8| (channel as u16 * intensity as u16 / 255) as u8;
+        Self::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+
+    /// Mixes `over` into this color with opacity `alpha`: 0 keeps this color, 255 gives `over`.
+    /// Glyph intensities are used as the alpha of the text color over the background.
+    pub fn blend(self, over: Color, alpha: u8) -> Self {
+        let mix = |under: u8, over: u8| {
+            let (under, over, alpha) = (under as u16, over as u16, alpha as u16);
+            ((under * (255 - alpha) + over * alpha) / 255) as u8
+        };
+        Self::new(mix(self.r, over.r), mix(self.g, over.g), mix(self.b, over.b))
+    }
+
+    /// Encodes the color as one pixel in `format`. Only the first `bytes_per_pixel` bytes of the
+    /// result belong to the pixel.
+    pub fn to_pixel(self, format: PixelFormat) -> [u8; 4] {
+        match format {
+            PixelFormat::Rgb => [self.r, self.g, self.b, 0],
+            PixelFormat::Bgr => [self.b, self.g, self.r, 0],
+            PixelFormat::U8 => [self.luminance(), 0, 0, 0],
+            PixelFormat::Unknown {
+                red_position,
+                green_position,
+                blue_position,
+            } => {
+                // each channel is 8 bits wide, starting at the given bit of the pixel
+                let channel = |value: u8, position: u8| {
+                    (value as u32).checked_shl(position as u32).unwrap_or(0)
+                };
+                let pixel = channel(self.r, red_position)
+                    | channel(self.g, green_position)
+                    | channel(self.b, blue_position);
+                pixel.to_le_bytes()
+            }
+            // formats added to the bootloader later are most likely 32-bit RGB variants
+            _ => [self.r, self.g, self.b, 0],
+        }
+    }
+
+    /// Approximate perceived brightness of the color.
+    pub fn luminance(self) -> u8 {
+        ((self.r as u16 * 77 + self.g as u16 * 150 + self.b as u16 * 29) >> 8) as u8
+    }
+}
+
+/// The standard and bright colors selected by SGR parameters 30–37 and 90–97, as xterm shows them.
+const ANSI_16: [Color; 16] = [
+    Color::new(0, 0, 0),
+    Color::new(205, 0, 0),
+    Color::new(0, 205, 0),
+    Color::new(205, 205, 0),
+    Color::new(0, 0, 238),
+    Color::new(205, 0, 205),
+    Color::new(0, 205, 205),
+    Color::new(229, 229, 229),
+    Color::new(127, 127, 127),
+    Color::new(255, 0, 0),
+    Color::new(0, 255, 0),
+    Color::new(255, 255, 0),
+    Color::new(92, 92, 255),
+    Color::new(255, 0, 255),
+    Color::new(0, 255, 255),
+    Color::new(255, 255, 255),
+];
@@ -0,0 +1,79 @@
+mod bmp;
+mod qoi;
+mod tga;
+
+use crate::graphics::{Bitmap, Color};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// Not a BMP, TGA or QOI file.
+    UnknownFormat,
+    /// A variant of the format that is not supported, such as a compressed BMP.
+    Unsupported,
+    /// The file ends before the image does.
+    Truncated,
+    /// The image has more pixels than the buffer it is decoded into.
+    TooLarge,
+}
+
+/// A decoded image. Transparent pixels are `None`; partly transparent ones are blended over
+/// black, the console background.
+pub struct Image<'a> {
+    width: usize,
+    height: usize,
+    pixels: &'a [Option<Color>],
+}
+
+impl Bitmap for Image<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Decodes a BMP, TGA or QOI file into `buffer`, recognising the format by its contents.
+pub fn decode<'a>(data: &[u8], buffer: &'a mut [Option<Color>]) -> Result<Image<'a>, ImageError> {
+    if data.starts_with(bmp::MAGIC) {
+        bmp::decode(data, buffer)
+    } else if data.starts_with(qoi::MAGIC) {
+        qoi::decode(data, buffer)
+    } else if tga::is_tga(data) {
+        tga::decode(data, buffer)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+/// Checks that a `width` × `height` image fits into `buffer` and returns the part it takes up.
+fn pixels_for(
+    buffer: &mut [Option<Color>],
+    width: usize,
+    height: usize,
+) -> Result<&mut [Option<Color>], ImageError> {
+    let count = width.checked_mul(height).ok_or(ImageError::TooLarge)?;
+    buffer.get_mut(..count).ok_or(ImageError::TooLarge)
+}
+
+/// Converts a pixel with straight alpha to how it is stored in an [Image].
+fn rgba(r: u8, g: u8, b: u8, alpha: u8) -> Option<Color> {
+    (alpha != 0).then(|| Color::new(r, g, b).scaled(alpha))
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn u16_le(data: &[u8], offset: usize) -> Result<u16, ImageError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ImageError::Truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn u32_le(data: &[u8], offset: usize) -> Result<u32, ImageError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ImageError::Truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
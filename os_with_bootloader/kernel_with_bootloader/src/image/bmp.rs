@@ -0,0 +1,102 @@
+//! Windows bitmaps: uncompressed, 8-bit paletted, 24-bit, and 32-bit with or without bit masks.
+
+use super::{pixels_for, rgba, u16_le, u32_le, Image, ImageError};
+use crate::graphics::Color;
+
+pub const MAGIC: &[u8] = b"BM";
+
+/// Offset of the DIB header, which follows the 14 byte file header.
+const DIB_HEADER: usize = 14;
+/// Size of BITMAPINFOHEADER, the oldest DIB header still in use.
+const INFO_HEADER_SIZE: usize = 40;
+
+const COMPRESSION_NONE: u32 = 0;
+const COMPRESSION_BITFIELDS: u32 = 3;
+
+/// Where the value of a channel sits in a 32-bit pixel.
+#[derive(Clone, Copy)]
+struct Mask(u32);
+
+impl Mask {
+    /// Extracts the channel from `pixel`, scaled to 8 bits.
+    fn extract(self, pixel: u32) -> u8 {
+        if self.0 == 0 {
+            return 0;
+        }
+        let value = (pixel & self.0) >> self.0.trailing_zeros();
+        let max = self.0 >> self.0.trailing_zeros();
+        (value as u64 * 255 / max as u64) as u8
+    }
+}
+
+pub fn decode<'a>(data: &[u8], buffer: &'a mut [Option<Color>]) -> Result<Image<'a>, ImageError> {
+    let pixel_offset = u32_le(data, 10)? as usize;
+    let header_size = u32_le(data, DIB_HEADER)? as usize;
+    if header_size < INFO_HEADER_SIZE {
+        return Err(ImageError::Unsupported);
+    }
+    let width = u32_le(data, DIB_HEADER + 4)? as i32;
+    let height = u32_le(data, DIB_HEADER + 8)? as i32;
+    let bits_per_pixel = u16_le(data, DIB_HEADER + 14)?;
+    let compression = u32_le(data, DIB_HEADER + 16)?;
+    if width <= 0 || height == 0 {
+        return Err(ImageError::Unsupported);
+    }
+    // positive heights are stored bottom row first
+    let bottom_up = height > 0;
+    let (width, height) = (width as usize, height.unsigned_abs() as usize);
+
+    // bit masks follow the info header, or are part of the larger V4 and V5 headers
+    let masks = match (compression, bits_per_pixel) {
+        (COMPRESSION_NONE, _) => None,
+        (COMPRESSION_BITFIELDS, 32) => {
+            let mask = |index: usize| u32_le(data, DIB_HEADER + INFO_HEADER_SIZE + index * 4);
+            let alpha = if header_size > INFO_HEADER_SIZE { mask(3)? } else { 0 };
+            Some([Mask(mask(0)?), Mask(mask(1)?), Mask(mask(2)?), Mask(alpha)])
+        }
+        _ => return Err(ImageError::Unsupported),
+    };
+    let palette = DIB_HEADER + header_size;
+    let bytes_per_pixel = match bits_per_pixel {
+        8 | 24 | 32 => bits_per_pixel as usize / 8,
+        _ => return Err(ImageError::Unsupported),
+    };
+    // rows are padded to a multiple of 4 bytes
+    let row_size = (width * bytes_per_pixel).div_ceil(4) * 4;
+
+    let pixels = pixels_for(buffer, width, height)?;
+    for y in 0..height {
+        let source_row = if bottom_up { height - 1 - y } else { y };
+        let start = source_row
+            .checked_mul(row_size)
+            .and_then(|offset| offset.checked_add(pixel_offset))
+            .ok_or(ImageError::Truncated)?;
+        let end = start.checked_add(width * bytes_per_pixel).ok_or(ImageError::Truncated)?;
+        let row = data.get(start..end).ok_or(ImageError::Truncated)?;
+        for (x, bytes) in row.chunks_exact(bytes_per_pixel).enumerate() {
+            pixels[y * width + x] = match (bytes, masks) {
+                (&[index], _) => {
+                    let entry = palette.checked_add(index as usize * 4);
+                    let color = entry
+                        .and_then(|entry| data.get(entry..entry.checked_add(3)?))
+                        .ok_or(ImageError::Truncated)?;
+                    rgba(color[2], color[1], color[0], u8::MAX)
+                }
+                (&[b, g, r], _) => rgba(r, g, b, u8::MAX),
+                (&[b0, b1, b2, b3], Some([red, green, blue, alpha])) => {
+                    let pixel = u32::from_le_bytes([b0, b1, b2, b3]);
+                    let opacity = if alpha.0 == 0 { u8::MAX } else { alpha.extract(pixel) };
+                    rgba(red.extract(pixel), green.extract(pixel), blue.extract(pixel), opacity)
+                }
+                // without masks the fourth byte is unused rather than alpha
+                (&[b, g, r, _], None) => rgba(r, g, b, u8::MAX),
+                _ => unreachable!("chunks have bytes_per_pixel bytes"),
+            };
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
@@ -0,0 +1,78 @@
+//! The Quite OK Image format, see <https://qoiformat.org/qoi-specification.pdf>.
+
+use super::{pixels_for, rgba, Image, ImageError};
+use crate::graphics::Color;
+
+pub const MAGIC: &[u8] = b"qoif";
+
+const HEADER_SIZE: usize = 14;
+
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+/// The top two bits of the other operations.
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_MASK: u8 = 0xc0;
+
+pub fn decode<'a>(data: &[u8], buffer: &'a mut [Option<Color>]) -> Result<Image<'a>, ImageError> {
+    let header = data.get(..HEADER_SIZE).ok_or(ImageError::Truncated)?;
+    let dimension = |offset: usize| {
+        let bytes = &header[offset..offset + 4];
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    let (width, height) = (dimension(4) as usize, dimension(8) as usize);
+    let pixels = pixels_for(buffer, width, height)?;
+
+    let mut position = HEADER_SIZE;
+    let mut next = || -> Result<u8, ImageError> {
+        let byte = *data.get(position).ok_or(ImageError::Truncated)?;
+        position += 1;
+        Ok(byte)
+    };
+    // previously seen pixels, indexed by a hash of their value
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut index = 0;
+    while index < pixels.len() {
+        let op = next()?;
+        let mut run = 1;
+        match op {
+            OP_RGB => pixel = [next()?, next()?, next()?, pixel[3]],
+            OP_RGBA => pixel = [next()?, next()?, next()?, next()?],
+            _ => match op & OP_MASK {
+                OP_INDEX => pixel = seen[op as usize],
+                OP_DIFF => {
+                    let diff = |shift: u8| ((op >> shift) & 0x03).wrapping_sub(2);
+                    pixel[0] = pixel[0].wrapping_add(diff(4));
+                    pixel[1] = pixel[1].wrapping_add(diff(2));
+                    pixel[2] = pixel[2].wrapping_add(diff(0));
+                }
+                OP_LUMA => {
+                    let green = (op & 0x3f).wrapping_sub(32);
+                    let red_blue = next()?;
+                    let red = green.wrapping_sub(8).wrapping_add(red_blue >> 4);
+                    let blue = green.wrapping_sub(8).wrapping_add(red_blue & 0x0f);
+                    pixel[0] = pixel[0].wrapping_add(red);
+                    pixel[1] = pixel[1].wrapping_add(green);
+                    pixel[2] = pixel[2].wrapping_add(blue);
+                }
+                OP_RUN => run = (op & 0x3f) as usize + 1,
+                _ => unreachable!("all four two-bit tags are handled"),
+            },
+        }
+        let [r, g, b, a] = pixel;
+        let hash = (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64;
+        seen[hash] = pixel;
+        for _ in 0..run.min(pixels.len() - index) {
+            pixels[index] = rgba(r, g, b, a);
+            index += 1;
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
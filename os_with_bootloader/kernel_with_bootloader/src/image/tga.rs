@@ -0,0 +1,92 @@
+//! Truevision TGA images: true-color (24/32-bit) and grayscale, raw or run-length encoded.
+
+use super::{pixels_for, rgba, u16_le, Image, ImageError};
+use crate::graphics::Color;
+
+const HEADER_SIZE: usize = 18;
+
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_GRAYSCALE: u8 = 3;
+const TYPE_RLE_TRUE_COLOR: u8 = 10;
+const TYPE_RLE_GRAYSCALE: u8 = 11;
+
+/// Set in the image descriptor if the first stored row is the top one.
+const DESCRIPTOR_TOP_DOWN: u8 = 0x20;
+/// Set in a run-length packet header if it repeats one pixel.
+const PACKET_RUN: u8 = 0x80;
+
+/// TGA files have no magic number, so this checks that the header describes an image this
+/// decoder supports.
+pub fn is_tga(data: &[u8]) -> bool {
+    let Some(header) = data.get(..HEADER_SIZE) else { return false };
+    let (color_map_type, image_type, bits_per_pixel) = (header[1], header[2], header[16]);
+    let supported = match image_type {
+        TYPE_TRUE_COLOR | TYPE_RLE_TRUE_COLOR => matches!(bits_per_pixel, 24 | 32),
+        TYPE_GRAYSCALE | TYPE_RLE_GRAYSCALE => bits_per_pixel == 8,
+        _ => false,
+    };
+    color_map_type <= 1 && supported
+}
+
+pub fn decode<'a>(data: &[u8], buffer: &'a mut [Option<Color>]) -> Result<Image<'a>, ImageError> {
+    let header = data.get(..HEADER_SIZE).ok_or(ImageError::Truncated)?;
+    let (id_length, color_map_type, image_type) = (header[0], header[1], header[2]);
+    let color_map_length = u16_le(header, 5)? as usize;
+    let color_map_bits = header[7] as usize;
+    let width = u16_le(header, 12)? as usize;
+    let height = u16_le(header, 14)? as usize;
+    let bytes_per_pixel = header[16] as usize / 8;
+    let top_down = header[17] & DESCRIPTOR_TOP_DOWN != 0;
+    let rle = matches!(image_type, TYPE_RLE_TRUE_COLOR | TYPE_RLE_GRAYSCALE);
+
+    // a color map is unused by true-color images but still takes up space
+    let color_map_size = if color_map_type == 1 {
+        color_map_length * color_map_bits.div_ceil(8)
+    } else {
+        0
+    };
+    let mut position = HEADER_SIZE + id_length as usize + color_map_size;
+    let pixels = pixels_for(buffer, width, height)?;
+    let next_pixel = |position: &mut usize| {
+        let bytes = data.get(*position..*position + bytes_per_pixel);
+        *position += bytes_per_pixel;
+        bytes.map(convert).ok_or(ImageError::Truncated)
+    };
+
+    // pixels are stored row by row, bottom row first unless the descriptor says otherwise
+    let mut index = 0;
+    while index < width * height {
+        let (count, repeated) = if rle {
+            let packet = *data.get(position).ok_or(ImageError::Truncated)?;
+            position += 1;
+            ((packet & !PACKET_RUN) as usize + 1, packet & PACKET_RUN != 0)
+        } else {
+            (1, false)
+        };
+        let mut pixel = next_pixel(&mut position)?;
+        for _ in 0..count.min(width * height - index) {
+            let (x, stored_row) = (index % width, index / width);
+            let y = if top_down { stored_row } else { height - 1 - stored_row };
+            pixels[y * width + x] = pixel;
+            index += 1;
+            if !repeated && index < width * height {
+                pixel = next_pixel(&mut position)?;
+            }
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Converts one stored pixel, blue first as in BMP files.
+fn convert(bytes: &[u8]) -> Option<Color> {
+    match *bytes {
+        [gray] => rgba(gray, gray, gray, u8::MAX),
+        [b, g, r] => rgba(r, g, b, u8::MAX),
+        [b, g, r, alpha] => rgba(r, g, b, alpha),
+        _ => None,
+    }
+}
@@ -0,0 +1,292 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use pic8259::ChainedPics;
+use spin::{Lazy, Mutex};
+use x86_64::instructions::{self, hlt, port::Port};
+use x86_64::registers::control::Cr2;
+use x86_64::structures::idt::{
+    HandlerFunc, InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode,
+};
+
+use crate::{apic, backtrace, eprintln, gdt, time, writer};
+
+static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
+    let mut idt = InterruptDescriptorTable::new();
+    idt.divide_error.set_handler_fn(divide_error_handler);
+    idt.debug.set_handler_fn(debug_handler);
+    idt.breakpoint.set_handler_fn(breakpoint_handler);
+    idt.overflow.set_handler_fn(overflow_handler);
+    idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+    idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+    idt.device_not_available.set_handler_fn(device_not_available_handler);
+    idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+    idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+    idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+    idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+    idt.page_fault.set_handler_fn(page_fault_handler);
+    idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+    idt.alignment_check.set_handler_fn(alignment_check_handler);
+    idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+    idt.virtualization.set_handler_fn(virtualization_handler);
+    idt.cp_protection_exception.set_handler_fn(cp_protection_handler);
+    idt.hv_injection_exception.set_handler_fn(hv_injection_handler);
+    idt.vmm_communication_exception.set_handler_fn(vmm_communication_handler);
+    idt.security_exception.set_handler_fn(security_exception_handler);
+    unsafe {
+        // a double fault is often caused by a stack overflow, so it needs a known-good stack
+        idt.double_fault
+            .set_handler_fn(double_fault_handler)
+            .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        idt.non_maskable_interrupt
+            .set_handler_fn(non_maskable_interrupt_handler)
+            .set_stack_index(gdt::NMI_IST_INDEX);
+        idt.machine_check
+            .set_handler_fn(machine_check_handler)
+            .set_stack_index(gdt::MACHINE_CHECK_IST_INDEX);
+    }
+    for (irq, &handler) in IRQ_ENTRY_POINTS.iter().enumerate() {
+        idt[irq_vector(irq as u8) as usize].set_handler_fn(handler);
+    }
+    idt[apic::SPURIOUS_VECTOR as usize].set_handler_fn(spurious_interrupt_handler);
+    idt
+});
+
+/// Vector of IRQ 0. The PICs are remapped here because vectors 0..32 belong to CPU exceptions.
+pub const PIC_1_OFFSET: u8 = 32;
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+/// Number of legacy ISA IRQ lines.
+pub const IRQ_COUNT: usize = 16;
+
+/// IRQ line the secondary PIC is chained to.
+const CASCADE_IRQ: u8 = 2;
+
+/// Command ports of the primary and secondary PIC.
+const PIC_1_COMMAND: u16 = 0x20;
+const PIC_2_COMMAND: u16 = 0xA0;
+/// OCW3 selecting the in-service register for the next read of the command port.
+const READ_ISR: u8 = 0x0B;
+/// Non-specific end of interrupt.
+const PIC_EOI: u8 = 0x20;
+
+pub static PICS: Mutex<ChainedPics> =
+    Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// Called from interrupt context whenever its IRQ line fires.
+pub type IrqHandler = fn();
+
+static IRQ_HANDLERS: Mutex<[Option<IrqHandler>; IRQ_COUNT]> = Mutex::new([None; IRQ_COUNT]);
+
+/// Set once IRQs are delivered by the I/O APIC instead of the 8259 PICs.
+static APIC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Interrupt vector that IRQ line `irq` is delivered on.
+pub const fn irq_vector(irq: u8) -> u8 {
+    PIC_1_OFFSET + irq
+}
+
+/// Loads the kernel's interrupt descriptor table.
+pub fn init_idt() {
+    IDT.load();
+}
+
+/// Remaps the 8259 PICs to [PIC_1_OFFSET] and masks every line without a handler.
+pub fn init_pics() {
+    let mut pics = PICS.lock();
+    unsafe {
+        pics.initialize();
+        pics.write_masks(!(1 << CASCADE_IRQ), 0xFF);
+    }
+}
+
+/// Masks the 8259 PICs for good (if the machine has them) and routes every IRQ line that already
+/// has a handler through the I/O APIC. Called by [apic::init] once the APICs are set up.
+pub fn switch_to_apic(has_legacy_pics: bool) {
+    instructions::interrupts::without_interrupts(|| {
+        if has_legacy_pics {
+            unsafe { PICS.lock().disable() };
+        }
+        APIC_ENABLED.store(true, Ordering::SeqCst);
+        let handlers = *IRQ_HANDLERS.lock();
+        for (irq, handler) in handlers.iter().enumerate() {
+            // the PIT stays masked, the local APIC timer raises the tick interrupt instead
+            if handler.is_some() && irq as u8 != time::TIMER_IRQ {
+                apic::route_irq(irq as u8, irq_vector(irq as u8));
+            }
+        }
+    });
+}
+
+/// Installs `handler` for IRQ line `irq` and unmasks the line, programming the I/O APIC
+/// redirection entry if the APIC is in use. The end of interrupt is sent after the handler
+/// returns.
+pub fn set_irq_handler(irq: u8, handler: IrqHandler) {
+    assert!((irq as usize) < IRQ_COUNT, "IRQ {} out of range", irq);
+    instructions::interrupts::without_interrupts(|| {
+        IRQ_HANDLERS.lock()[irq as usize] = Some(handler);
+        if APIC_ENABLED.load(Ordering::SeqCst) {
+            apic::route_irq(irq, irq_vector(irq));
+            return;
+        }
+        let mut pics = PICS.lock();
+        unsafe {
+            let [mut primary, mut secondary] = pics.read_masks();
+            if irq < 8 {
+                primary &= !(1 << irq);
+            } else {
+                secondary &= !(1 << (irq - 8));
+            }
+            pics.write_masks(primary, secondary);
+        }
+    });
+}
+
+/// Acknowledges IRQ line `irq` so that the controller delivers further interrupts.
+pub fn end_of_interrupt(irq: u8) {
+    if APIC_ENABLED.load(Ordering::Relaxed) {
+        apic::end_of_interrupt();
+    } else {
+        unsafe { PICS.lock().notify_end_of_interrupt(irq_vector(irq)) };
+    }
+}
+
+/// Whether the 8259 raised IRQ7 or IRQ15 for real. A request that goes away before the CPU
+/// acknowledges it is still delivered on the lowest priority line of its PIC, but without
+/// setting that line's in-service bit.
+fn pic_in_service(irq: u8) -> bool {
+    let port = if irq < 8 { PIC_1_COMMAND } else { PIC_2_COMMAND };
+    let _pics = PICS.lock();
+    let mut command = Port::<u8>::new(port);
+    unsafe {
+        command.write(READ_ISR);
+        command.read() & (1 << (irq % 8)) != 0
+    }
+}
+
+fn dispatch_irq(irq: u8) {
+    let legacy = !APIC_ENABLED.load(Ordering::Relaxed);
+    if legacy && matches!(irq, 7 | 15) && !pic_in_service(irq) {
+        // a spurious IRQ15 did come through the primary PIC's cascade line, which is in service
+        if irq == 15 {
+            let _pics = PICS.lock();
+            unsafe { Port::<u8>::new(PIC_1_COMMAND).write(PIC_EOI) };
+        }
+        return;
+    }
+    // copy the handler out so it may register handlers itself
+    let handler = IRQ_HANDLERS.lock()[irq as usize];
+    if let Some(handler) = handler {
+        handler();
+    }
+    end_of_interrupt(irq);
+}
+
+/// Spurious interrupts from the local APIC carry no work and must not be acknowledged.
+extern "x86-interrupt" fn spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {}
+
+/// Defines one IDT entry point per IRQ line, all forwarding to [dispatch_irq].
+macro_rules! irq_entry_points {
+    ($($irq:literal => $handler:ident),* $(,)?) => {
+        $(
+            extern "x86-interrupt" fn $handler(_stack_frame: InterruptStackFrame) {
+                dispatch_irq($irq);
+            }
+        )*
+        const IRQ_ENTRY_POINTS: [HandlerFunc; IRQ_COUNT] = [$($handler),*];
+    };
+}
+
+irq_entry_points!(
+    0 => irq0_handler, 1 => irq1_handler, 2 => irq2_handler, 3 => irq3_handler,
+    4 => irq4_handler, 5 => irq5_handler, 6 => irq6_handler, 7 => irq7_handler,
+    8 => irq8_handler, 9 => irq9_handler, 10 => irq10_handler, 11 => irq11_handler,
+    12 => irq12_handler, 13 => irq13_handler, 14 => irq14_handler, 15 => irq15_handler,
+);
+
+fn report_exception(name: &str, error_code: Option<u64>, stack_frame: &InterruptStackFrame) {
+    eprintln!("EXCEPTION: {}", name);
+    if let Some(error_code) = error_code {
+        eprintln!("Error code: {:#x}", error_code);
+    }
+    eprintln!("RIP: {:#x}", stack_frame.instruction_pointer.as_u64());
+    eprintln!("{:#?}", stack_frame);
+    eprintln!("Backtrace:");
+    writer::with_writer_forced(|writer| {
+        let _ = backtrace::write_interrupted(writer, stack_frame);
+        writer.flush();
+    });
+}
+
+/// Reports an exception the kernel cannot recover from and stops the CPU.
+fn fatal_exception(name: &str, error_code: Option<u64>, stack_frame: &InterruptStackFrame) -> ! {
+    report_exception(name, error_code, stack_frame);
+    halt_forever()
+}
+
+fn halt_forever() -> ! {
+    instructions::interrupts::disable();
+    loop {
+        hlt();
+    }
+}
+
+/// Defines a handler that reports the exception through [fatal_exception].
+macro_rules! fatal_handler {
+    ($handler:ident, $name:expr) => {
+        extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame) {
+            fatal_exception($name, None, &stack_frame);
+        }
+    };
+    ($handler:ident, $name:expr, error_code) => {
+        extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame, error_code: u64) {
+            fatal_exception($name, Some(error_code), &stack_frame);
+        }
+    };
+}
+
+fatal_handler!(divide_error_handler, "DIVIDE ERROR");
+fatal_handler!(debug_handler, "DEBUG");
+fatal_handler!(non_maskable_interrupt_handler, "NON-MASKABLE INTERRUPT");
+fatal_handler!(overflow_handler, "OVERFLOW");
+fatal_handler!(bound_range_exceeded_handler, "BOUND RANGE EXCEEDED");
+fatal_handler!(invalid_opcode_handler, "INVALID OPCODE");
+fatal_handler!(device_not_available_handler, "DEVICE NOT AVAILABLE");
+fatal_handler!(invalid_tss_handler, "INVALID TSS", error_code);
+fatal_handler!(segment_not_present_handler, "SEGMENT NOT PRESENT", error_code);
+fatal_handler!(stack_segment_fault_handler, "STACK SEGMENT FAULT", error_code);
+fatal_handler!(general_protection_fault_handler, "GENERAL PROTECTION FAULT", error_code);
+fatal_handler!(x87_floating_point_handler, "x87 FLOATING POINT");
+fatal_handler!(alignment_check_handler, "ALIGNMENT CHECK", error_code);
+fatal_handler!(simd_floating_point_handler, "SIMD FLOATING POINT");
+fatal_handler!(virtualization_handler, "VIRTUALIZATION");
+fatal_handler!(cp_protection_handler, "CONTROL PROTECTION", error_code);
+fatal_handler!(hv_injection_handler, "HYPERVISOR INJECTION");
+fatal_handler!(vmm_communication_handler, "VMM COMMUNICATION", error_code);
+fatal_handler!(security_exception_handler, "SECURITY EXCEPTION", error_code);
+
+/// `int3` is used for debugging, so execution continues after it has been reported.
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    report_exception("BREAKPOINT", None, &stack_frame);
+}
+
+extern "x86-interrupt" fn page_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: PageFaultErrorCode,
+) {
+    report_exception("PAGE FAULT", Some(error_code.bits()), &stack_frame);
+    // CR2 holds the address whose access faulted
+    eprintln!("Accessed address (CR2): {:#x}", Cr2::read().as_u64());
+    eprintln!("Page fault flags: {:?}", error_code);
+    halt_forever()
+}
+
+extern "x86-interrupt" fn double_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) -> ! {
+    fatal_exception("DOUBLE FAULT", Some(error_code), &stack_frame);
+}
+
+extern "x86-interrupt" fn machine_check_handler(stack_frame: InterruptStackFrame) -> ! {
+    fatal_exception("MACHINE CHECK", None, &stack_frame);
+}
@@ -0,0 +1,288 @@
+mod layout;
+mod scancode;
+
+pub use layout::Layout;
+pub use scancode::{KeyCode, KeyState};
+
+use core::hint;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::{Context, Poll};
+
+use futures_util::{Stream, StreamExt};
+use spin::Mutex;
+use x86_64::instructions::{interrupts, port::Port};
+
+use crate::interrupts::set_irq_handler;
+use crate::task::{self, InputQueue, Task};
+use crate::writer;
+use scancode::Decoder;
+
+/// IRQ line of the first PS/2 port.
+const KEYBOARD_IRQ: u8 = 1;
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+
+const COMMAND_SET_LEDS: u8 = 0xED;
+const RESPONSE_ACK: u8 = 0xFA;
+const RESPONSE_RESEND: u8 = 0xFE;
+
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// Number of key events buffered until the kernel reads them; further events are dropped.
+const EVENT_QUEUE_SIZE: usize = 128;
+/// Number of raw scancodes buffered for [ScancodeStream]; further bytes are dropped.
+const SCANCODE_QUEUE_SIZE: usize = 128;
+
+/// Which modifier keys are held and which lock keys are active.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    pub fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+
+    /// Right alt selects the third level of non-US layouts.
+    pub fn alt_gr(&self) -> bool {
+        self.right_alt
+    }
+
+    fn leds(&self) -> u8 {
+        (self.scroll_lock as u8 * LED_SCROLL_LOCK)
+            | (self.num_lock as u8 * LED_NUM_LOCK)
+            | (self.caps_lock as u8 * LED_CAPS_LOCK)
+    }
+}
+
+/// A key press or release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub state: KeyState,
+    /// Modifier state after this event was applied.
+    pub modifiers: Modifiers,
+    /// The character the key produces in the active layout, for presses only.
+    pub character: Option<char>,
+}
+
+struct Keyboard {
+    decoder: Decoder,
+    modifiers: Modifiers,
+    /// LED state waiting for the keyboard to acknowledge the set-LEDs command.
+    pending_leds: Option<u8>,
+}
+
+static KEYBOARD: Mutex<Keyboard> = Mutex::new(Keyboard {
+    decoder: Decoder::new(),
+    modifiers: Modifiers {
+        left_shift: false,
+        right_shift: false,
+        left_ctrl: false,
+        right_ctrl: false,
+        left_alt: false,
+        right_alt: false,
+        caps_lock: false,
+        num_lock: true,
+        scroll_lock: false,
+    },
+    pending_leds: None,
+});
+
+static EVENTS: InputQueue<KeyEvent, EVENT_QUEUE_SIZE> = InputQueue::new();
+static SCANCODES: InputQueue<u8, SCANCODE_QUEUE_SIZE> = InputQueue::new();
+
+static LAYOUT: AtomicU8 = AtomicU8::new(Layout::Us as u8);
+
+/// Discards stale input, spawns the task that decodes key presses and starts handling IRQ1.
+pub fn init() {
+    let mut status = Port::<u8>::new(STATUS_PORT);
+    let mut data = Port::<u8>::new(DATA_PORT);
+    while unsafe { status.read() } & STATUS_OUTPUT_FULL != 0 {
+        unsafe { data.read() };
+    }
+    // sync the LEDs with the initial lock state; the IRQ handler sends them once acknowledged
+    {
+        let mut keyboard = KEYBOARD.lock();
+        keyboard.pending_leds = Some(keyboard.modifiers.leds());
+    }
+    write_data(COMMAND_SET_LEDS);
+    if task::spawn(Task::new(decode_scancodes())).is_err() {
+        log::error!("no room for the keyboard task, key presses will be ignored");
+    }
+    set_irq_handler(KEYBOARD_IRQ, on_interrupt);
+}
+
+/// Selects the layout used to turn key presses into characters.
+pub fn set_layout(layout: Layout) {
+    LAYOUT.store(layout as u8, Ordering::Relaxed);
+}
+
+pub fn layout() -> Layout {
+    match LAYOUT.load(Ordering::Relaxed) {
+        x if x == Layout::Uk as u8 => Layout::Uk,
+        x if x == Layout::Azerty as u8 => Layout::Azerty,
+        _ => Layout::Us,
+    }
+}
+
+/// Key events in the order they happened. Only one task may read them.
+pub struct KeyEvents(());
+
+pub fn events() -> KeyEvents {
+    KeyEvents(())
+}
+
+impl Stream for KeyEvents {
+    type Item = KeyEvent;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<KeyEvent>> {
+        EVENTS.poll_pop(context).map(Some)
+    }
+}
+
+/// The bytes the keyboard sends, without the replies to commands. Read by [decode_scancodes].
+struct ScancodeStream(());
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<u8>> {
+        SCANCODES.poll_pop(context).map(Some)
+    }
+}
+
+/// Sends a byte to the keyboard, waiting briefly for the controller to accept it.
+fn write_data(byte: u8) {
+    let mut status = Port::<u8>::new(STATUS_PORT);
+    for _ in 0..10_000 {
+        if unsafe { status.read() } & STATUS_INPUT_FULL == 0 {
+            break;
+        }
+        hint::spin_loop();
+    }
+    unsafe { Port::new(DATA_PORT).write(byte) };
+}
+
+fn on_interrupt() {
+    let byte: u8 = unsafe { Port::new(DATA_PORT).read() };
+    let mut keyboard = KEYBOARD.lock();
+
+    match byte {
+        RESPONSE_ACK => {
+            if let Some(leds) = keyboard.pending_leds.take() {
+                write_data(leds);
+            }
+            return;
+        }
+        RESPONSE_RESEND => {
+            if keyboard.pending_leds.is_some() {
+                write_data(COMMAND_SET_LEDS);
+            }
+            return;
+        }
+        _ => {}
+    }
+    // decoding is left to the keyboard task; a full queue means it is not keeping up anyway
+    let _ = SCANCODES.push(byte);
+}
+
+/// Turns scancodes into key events, forever. Runs as a task, so that the interrupt handler only
+/// has to queue the bytes.
+async fn decode_scancodes() {
+    let mut scancodes = ScancodeStream(());
+    while let Some(byte) = scancodes.next().await {
+        // the interrupt handler takes the keyboard as well
+        interrupts::without_interrupts(|| decode_scancode(byte));
+    }
+}
+
+fn decode_scancode(byte: u8) {
+    let mut keyboard = KEYBOARD.lock();
+    let Some((code, state)) = keyboard.decoder.advance(byte) else { return };
+    let down = state == KeyState::Down;
+    let modifiers = &mut keyboard.modifiers;
+    let mut leds_changed = false;
+    match code {
+        KeyCode::LeftShift => modifiers.left_shift = down,
+        KeyCode::RightShift => modifiers.right_shift = down,
+        KeyCode::LeftCtrl => modifiers.left_ctrl = down,
+        KeyCode::RightCtrl => modifiers.right_ctrl = down,
+        KeyCode::LeftAlt => modifiers.left_alt = down,
+        KeyCode::RightAlt => modifiers.right_alt = down,
+        KeyCode::CapsLock if down => {
+            modifiers.caps_lock = !modifiers.caps_lock;
+            leds_changed = true;
+        }
+        KeyCode::NumLock if down => {
+            modifiers.num_lock = !modifiers.num_lock;
+            leds_changed = true;
+        }
+        KeyCode::ScrollLock if down => {
+            modifiers.scroll_lock = !modifiers.scroll_lock;
+            leds_changed = true;
+        }
+        _ => {}
+    }
+    let modifiers = *modifiers;
+    if leds_changed {
+        keyboard.pending_leds = Some(modifiers.leds());
+        write_data(COMMAND_SET_LEDS);
+    }
+
+    // Alt+F1 to Alt+F6 switch virtual consoles and are not passed on
+    if let Some(console) = console_key(code).filter(|_| down && modifiers.alt()) {
+        writer::request_switch(console);
+        return;
+    }
+
+    let character = if down {
+        layout().translate(code, &modifiers)
+    } else {
+        None
+    };
+    // a full queue means nobody is reading input, so dropping the event is fine
+    let _ = EVENTS.push(KeyEvent {
+        code,
+        state,
+        modifiers,
+        character,
+    });
+}
+
+/// The virtual console a function key selects together with Alt.
+fn console_key(code: KeyCode) -> Option<usize> {
+    let console = match code {
+        KeyCode::F1 => 0,
+        KeyCode::F2 => 1,
+        KeyCode::F3 => 2,
+        KeyCode::F4 => 3,
+        KeyCode::F5 => 4,
+        KeyCode::F6 => 5,
+        _ => return None,
+    };
+    (console < writer::CONSOLES).then_some(console)
+}
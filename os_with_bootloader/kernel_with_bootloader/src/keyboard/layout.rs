@@ -0,0 +1,210 @@
+use super::scancode::KeyCode;
+use super::Modifiers;
+use crate::writer::constants::font_constants::BACKSPACE;
+
+/// Keyboard layouts the driver can translate key presses with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// US QWERTY.
+    Us,
+    /// UK QWERTY.
+    Uk,
+    /// French AZERTY.
+    Azerty,
+}
+
+/// What a key produces without modifiers, with shift, and with AltGr.
+struct Legends {
+    normal: char,
+    shifted: char,
+    alt_gr: Option<char>,
+}
+
+const fn keys(normal: char, shifted: char) -> Option<Legends> {
+    Some(Legends {
+        normal,
+        shifted,
+        alt_gr: None,
+    })
+}
+
+const fn keys_alt_gr(normal: char, shifted: char, alt_gr: char) -> Option<Legends> {
+    Some(Legends {
+        normal,
+        shifted,
+        alt_gr: Some(alt_gr),
+    })
+}
+
+impl Layout {
+    /// Returns the character `code` produces with the given modifiers, if it produces one.
+    pub fn translate(self, code: KeyCode, modifiers: &Modifiers) -> Option<char> {
+        if let Some(c) = layout_independent(code, modifiers) {
+            return Some(c);
+        }
+        let legends = match self {
+            Layout::Us => us(code),
+            Layout::Uk => uk(code),
+            Layout::Azerty => azerty(code),
+        }?;
+        if modifiers.alt_gr() {
+            return legends.alt_gr;
+        }
+        // caps lock only inverts shift for letters
+        let shifted = modifiers.shift() ^ (modifiers.caps_lock && legends.normal.is_alphabetic());
+        let c = if shifted {
+            legends.shifted
+        } else {
+            legends.normal
+        };
+        if modifiers.ctrl() && c.is_ascii_alphabetic() {
+            // Ctrl+A..Ctrl+Z are the control characters 0x01..0x1A
+            return char::from_u32(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1);
+        }
+        Some(c)
+    }
+}
+
+/// Keys that produce the same character on every supported layout.
+fn layout_independent(code: KeyCode, modifiers: &Modifiers) -> Option<char> {
+    use KeyCode::*;
+    let keypad_digit = |digit: char| modifiers.num_lock.then_some(digit);
+    match code {
+        Space => Some(' '),
+        Tab => Some('\t'),
+        Enter | KeypadEnter => Some('\n'),
+        Backspace => Some(BACKSPACE),
+        Escape => Some('\u{1b}'),
+        Delete => Some('\u{7f}'),
+        KeypadDivide => Some('/'),
+        KeypadMultiply => Some('*'),
+        KeypadMinus => Some('-'),
+        KeypadPlus => Some('+'),
+        KeypadPeriod => keypad_digit('.'),
+        Keypad0 => keypad_digit('0'),
+        Keypad1 => keypad_digit('1'),
+        Keypad2 => keypad_digit('2'),
+        Keypad3 => keypad_digit('3'),
+        Keypad4 => keypad_digit('4'),
+        Keypad5 => keypad_digit('5'),
+        Keypad6 => keypad_digit('6'),
+        Keypad7 => keypad_digit('7'),
+        Keypad8 => keypad_digit('8'),
+        Keypad9 => keypad_digit('9'),
+        _ => None,
+    }
+}
+
+/// Letters that are in the same place on the QWERTY layouts.
+fn qwerty_letter(code: KeyCode) -> Option<Legends> {
+    use KeyCode::*;
+    let letter = match code {
+        Q => 'q',
+        W => 'w',
+        E => 'e',
+        R => 'r',
+        T => 't',
+        Y => 'y',
+        U => 'u',
+        I => 'i',
+        O => 'o',
+        P => 'p',
+        A => 'a',
+        S => 's',
+        D => 'd',
+        F => 'f',
+        G => 'g',
+        H => 'h',
+        J => 'j',
+        K => 'k',
+        L => 'l',
+        Z => 'z',
+        X => 'x',
+        C => 'c',
+        V => 'v',
+        B => 'b',
+        N => 'n',
+        M => 'm',
+        _ => return None,
+    };
+    keys(letter, letter.to_ascii_uppercase())
+}
+
+fn us(code: KeyCode) -> Option<Legends> {
+    use KeyCode::*;
+    match code {
+        Backtick => keys('`', '~'),
+        Digit1 => keys('1', '!'),
+        Digit2 => keys('2', '@'),
+        Digit3 => keys('3', '#'),
+        Digit4 => keys('4', '$'),
+        Digit5 => keys('5', '%'),
+        Digit6 => keys('6', '^'),
+        Digit7 => keys('7', '&'),
+        Digit8 => keys('8', '*'),
+        Digit9 => keys('9', '('),
+        Digit0 => keys('0', ')'),
+        Minus => keys('-', '_'),
+        Equals => keys('=', '+'),
+        LeftBracket => keys('[', '{'),
+        RightBracket => keys(']', '}'),
+        Backslash | NonUsBackslash => keys('\\', '|'),
+        Semicolon => keys(';', ':'),
+        Quote => keys('\'', '"'),
+        Comma => keys(',', '<'),
+        Period => keys('.', '>'),
+        Slash => keys('/', '?'),
+        _ => qwerty_letter(code),
+    }
+}
+
+fn uk(code: KeyCode) -> Option<Legends> {
+    use KeyCode::*;
+    match code {
+        Backtick => keys_alt_gr('`', '¬', '¦'),
+        Digit2 => keys('2', '"'),
+        Digit3 => keys('3', '£'),
+        Digit4 => keys_alt_gr('4', '$', '€'),
+        Quote => keys('\'', '@'),
+        Backslash => keys('#', '~'),
+        NonUsBackslash => keys('\\', '|'),
+        E => keys_alt_gr('e', 'E', 'é'),
+        _ => us(code),
+    }
+}
+
+fn azerty(code: KeyCode) -> Option<Legends> {
+    use KeyCode::*;
+    match code {
+        Backtick => keys('²', '²'),
+        Digit1 => keys('&', '1'),
+        Digit2 => keys_alt_gr('é', '2', '~'),
+        Digit3 => keys_alt_gr('"', '3', '#'),
+        Digit4 => keys_alt_gr('\'', '4', '{'),
+        Digit5 => keys_alt_gr('(', '5', '['),
+        Digit6 => keys_alt_gr('-', '6', '|'),
+        Digit7 => keys_alt_gr('è', '7', '`'),
+        Digit8 => keys_alt_gr('_', '8', '\\'),
+        Digit9 => keys_alt_gr('ç', '9', '^'),
+        Digit0 => keys_alt_gr('à', '0', '@'),
+        Minus => keys_alt_gr(')', '°', ']'),
+        Equals => keys_alt_gr('=', '+', '}'),
+        Q => keys('a', 'A'),
+        W => keys('z', 'Z'),
+        E => keys_alt_gr('e', 'E', '€'),
+        // dead keys are not supported, so ^ and ¨ are produced directly
+        LeftBracket => keys('^', '¨'),
+        RightBracket => keys_alt_gr('$', '£', '¤'),
+        A => keys('q', 'Q'),
+        Semicolon => keys('m', 'M'),
+        Quote => keys('ù', '%'),
+        Backslash => keys('*', 'µ'),
+        NonUsBackslash => keys('<', '>'),
+        Z => keys('w', 'W'),
+        M => keys(',', '?'),
+        Comma => keys(';', '.'),
+        Period => keys(':', '/'),
+        Slash => keys('!', '§'),
+        _ => qwerty_letter(code),
+    }
+}
@@ -0,0 +1,302 @@
+/// A physical key, named after its legend on a US QWERTY keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Backtick,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Digit0,
+    Minus,
+    Equals,
+    Backspace,
+    Tab,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    CapsLock,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Quote,
+    Enter,
+    LeftShift,
+    /// The extra key next to left shift on ISO keyboards.
+    NonUsBackslash,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    RightShift,
+    LeftCtrl,
+    LeftGui,
+    LeftAlt,
+    Space,
+    /// Right alt, which acts as AltGr on most non-US layouts.
+    RightAlt,
+    RightGui,
+    Menu,
+    RightCtrl,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    Insert,
+    Home,
+    PageUp,
+    Delete,
+    End,
+    PageDown,
+    ArrowUp,
+    ArrowLeft,
+    ArrowDown,
+    ArrowRight,
+    NumLock,
+    KeypadDivide,
+    KeypadMultiply,
+    KeypadMinus,
+    KeypadPlus,
+    KeypadEnter,
+    KeypadPeriod,
+    Keypad0,
+    Keypad1,
+    Keypad2,
+    Keypad3,
+    Keypad4,
+    Keypad5,
+    Keypad6,
+    Keypad7,
+    Keypad8,
+    Keypad9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+}
+
+/// Translates a scancode set 1 make code without prefix.
+fn key_code(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match code {
+        0x01 => Escape,
+        0x02 => Digit1,
+        0x03 => Digit2,
+        0x04 => Digit3,
+        0x05 => Digit4,
+        0x06 => Digit5,
+        0x07 => Digit6,
+        0x08 => Digit7,
+        0x09 => Digit8,
+        0x0A => Digit9,
+        0x0B => Digit0,
+        0x0C => Minus,
+        0x0D => Equals,
+        0x0E => Backspace,
+        0x0F => Tab,
+        0x10 => Q,
+        0x11 => W,
+        0x12 => E,
+        0x13 => R,
+        0x14 => T,
+        0x15 => Y,
+        0x16 => U,
+        0x17 => I,
+        0x18 => O,
+        0x19 => P,
+        0x1A => LeftBracket,
+        0x1B => RightBracket,
+        0x1C => Enter,
+        0x1D => LeftCtrl,
+        0x1E => A,
+        0x1F => S,
+        0x20 => D,
+        0x21 => F,
+        0x22 => G,
+        0x23 => H,
+        0x24 => J,
+        0x25 => K,
+        0x26 => L,
+        0x27 => Semicolon,
+        0x28 => Quote,
+        0x29 => Backtick,
+        0x2A => LeftShift,
+        0x2B => Backslash,
+        0x2C => Z,
+        0x2D => X,
+        0x2E => C,
+        0x2F => V,
+        0x30 => B,
+        0x31 => N,
+        0x32 => M,
+        0x33 => Comma,
+        0x34 => Period,
+        0x35 => Slash,
+        0x36 => RightShift,
+        0x37 => KeypadMultiply,
+        0x38 => LeftAlt,
+        0x39 => Space,
+        0x3A => CapsLock,
+        0x3B => F1,
+        0x3C => F2,
+        0x3D => F3,
+        0x3E => F4,
+        0x3F => F5,
+        0x40 => F6,
+        0x41 => F7,
+        0x42 => F8,
+        0x43 => F9,
+        0x44 => F10,
+        0x45 => NumLock,
+        0x46 => ScrollLock,
+        0x47 => Keypad7,
+        0x48 => Keypad8,
+        0x49 => Keypad9,
+        0x4A => KeypadMinus,
+        0x4B => Keypad4,
+        0x4C => Keypad5,
+        0x4D => Keypad6,
+        0x4E => KeypadPlus,
+        0x4F => Keypad1,
+        0x50 => Keypad2,
+        0x51 => Keypad3,
+        0x52 => Keypad0,
+        0x53 => KeypadPeriod,
+        0x56 => NonUsBackslash,
+        0x57 => F11,
+        0x58 => F12,
+        _ => return None,
+    })
+}
+
+/// Translates a scancode set 1 make code that followed an `E0` prefix.
+fn extended_key_code(code: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match code {
+        0x1C => KeypadEnter,
+        0x1D => RightCtrl,
+        0x35 => KeypadDivide,
+        0x37 => PrintScreen,
+        0x38 => RightAlt,
+        0x47 => Home,
+        0x48 => ArrowUp,
+        0x49 => PageUp,
+        0x4B => ArrowLeft,
+        0x4D => ArrowRight,
+        0x4F => End,
+        0x50 => ArrowDown,
+        0x51 => PageDown,
+        0x52 => Insert,
+        0x53 => Delete,
+        0x5B => LeftGui,
+        0x5C => RightGui,
+        0x5D => Menu,
+        // 0x2A and 0x36 are fake shifts sent around PrintScreen and friends
+        _ => return None,
+    })
+}
+
+const EXTENDED_PREFIX: u8 = 0xE0;
+/// Starts the six byte Pause sequence `E1 1D 45 E1 9D C5`.
+const PAUSE_PREFIX: u8 = 0xE1;
+const PAUSE_SEQUENCE_REMAINING: u8 = 5;
+const BREAK_BIT: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy)]
+enum DecoderState {
+    Start,
+    Extended,
+    /// Inside the Pause sequence with this many bytes left.
+    Pause(u8),
+}
+
+/// Turns the byte stream from the keyboard into key presses and releases.
+pub struct Decoder {
+    state: DecoderState,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            state: DecoderState::Start,
+        }
+    }
+
+    /// Feeds one byte; returns the key event it completes, if any.
+    pub fn advance(&mut self, byte: u8) -> Option<(KeyCode, KeyState)> {
+        let key_state = |byte: u8| {
+            if byte & BREAK_BIT != 0 {
+                KeyState::Up
+            } else {
+                KeyState::Down
+            }
+        };
+        match self.state {
+            DecoderState::Start => match byte {
+                EXTENDED_PREFIX => {
+                    self.state = DecoderState::Extended;
+                    None
+                }
+                PAUSE_PREFIX => {
+                    self.state = DecoderState::Pause(PAUSE_SEQUENCE_REMAINING);
+                    None
+                }
+                _ => Some((key_code(byte & !BREAK_BIT)?, key_state(byte))),
+            },
+            DecoderState::Extended => {
+                self.state = DecoderState::Start;
+                Some((extended_key_code(byte & !BREAK_BIT)?, key_state(byte)))
+            }
+            DecoderState::Pause(remaining) => {
+                if remaining > 1 {
+                    self.state = DecoderState::Pause(remaining - 1);
+                    return None;
+                }
+                // Pause has no break code, so it is reported as a press only
+                self.state = DecoderState::Start;
+                Some((KeyCode::Pause, KeyState::Down))
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use core::fmt::Write;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError, STATIC_MAX_LEVEL};
+
+use crate::console::SinkWriter;
+use crate::time;
+use crate::writer::{self, Color};
+
+/// Level used until [set_level] is called. Can never exceed the compile-time limit chosen with
+/// the `log_max_level_*` features.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// `log` backend that writes to the log console of the framebuffer and, through it, to every
+/// console sink.
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+
+/// Installs the kernel logger. Must only be called once.
+pub fn init() -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    set_level(DEFAULT_LEVEL);
+    Ok(())
+}
+
+/// Changes the runtime filter. Records above the compile-time limit stay compiled out.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level.min(STATIC_MAX_LEVEL));
+}
+
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::RED,
+        Level::Warn => Color::YELLOW,
+        Level::Info => Color::GREEN,
+        Level::Debug => Color::CYAN,
+        Level::Trace => Color::GRAY,
+    }
+}
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let uptime = time::uptime();
+        let module = record.module_path().unwrap_or_else(|| record.target());
+
+        let written = writer::with_console(writer::LOG_CONSOLE, |writer| {
+            let _ = write!(writer, "[{:>5}.{:06}] ", uptime.as_secs(), uptime.subsec_micros());
+            let previous = writer.foreground();
+            writer.set_foreground(level_color(record.level()));
+            let _ = write!(writer, "{:<5}", record.level());
+            writer.set_foreground(previous);
+            let _ = writeln!(writer, " {}: {}", module, record.args());
+        });
+        if written.is_none() {
+            let _ = writeln!(
+                SinkWriter,
+                "[{:>5}.{:06}] {:<5} {}: {}",
+                uptime.as_secs(),
+                uptime.subsec_micros(),
+                record.level(),
+                module,
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -1,13 +1,41 @@
 #![no_std]
 #![no_main]
+#![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
 // #[macro_use]
 // #[no_mangle]
 
+extern crate alloc;
+
+mod acpi;
+mod allocator;
+mod apic;
+mod backtrace;
+mod console;
+mod gdt;
+mod graphics;
+mod image;
+mod interrupts;
+mod keyboard;
+mod logger;
+mod memory;
+mod panic;
+mod registry;
+mod ring_buffer;
+mod screen;
+mod serial;
+mod shell;
+mod splash;
+mod task;
+mod time;
 mod writer;
 
+use core::slice;
+
 use bootloader_api::config::Mapping;
-use writer::FrameBufferWriter;
-use x86_64::instructions::hlt;
+use task::Task;
+use writer::font::PsfError;
+use x86_64::VirtAddr;
 
 //Use the entry_point macro to register the entry point function: bootloader_api::entry_point!(kernel_main)
 //optionally pass a custom config
@@ -21,46 +49,83 @@ bootloader_api::entry_point!(my_entry_point, config = &BOOTLOADER_CONFIG);
 
 fn my_entry_point(boot_info: &'static mut bootloader_api::BootInfo) -> ! {
     
-    let frame_buffer_info = boot_info.framebuffer.as_mut().unwrap().info();
-    
-    let buffer = boot_info.framebuffer.as_mut().unwrap().buffer_mut();
-
-    let mut frame_buffer_writer=
-     FrameBufferWriter::new(buffer, frame_buffer_info);
-     
-     use core::fmt::Write;//below requires this 
-     writeln!(frame_buffer_writer, "Testing testing {} and {}", 1, 4.0/2.0).unwrap();
-
-     macro_rules! print {
-        ($($a: expr), *) => {
-            write!(frame_buffer_writer, $($a), *).unwrap();
-        };
+    // ACPI tables, APIC registers and the page tables backtraces check are reached through the
+    // physical memory mapping
+    memory::init(*boot_info.physical_memory_offset.as_ref().unwrap(), &boot_info.memory_regions);
+    // the memory map may count these as usable RAM, but they stay in use for good
+    if let Some(framebuffer) = boot_info.framebuffer.as_ref() {
+        let buffer = framebuffer.buffer();
+        memory::frames::reserve_mapped(VirtAddr::from_ptr(buffer.as_ptr()), buffer.len() as u64);
+    }
+    if let Some(&ramdisk) = boot_info.ramdisk_addr.as_ref() {
+        memory::frames::reserve_mapped(VirtAddr::new(ramdisk), boot_info.ramdisk_len);
+    }
+    // bring up serial first so everything the framebuffer shows is also captured there
+    let serial_result = serial::init();
+    writer::init(boot_info.framebuffer.as_mut().unwrap());
+    logger::init().unwrap();
+    if let Err(error) = allocator::init() {
+        log::error!("cannot map the kernel heap: {:?}", error);
     }
-    macro_rules! println {
-        ($($a: expr), *) => {
-            writeln!(frame_buffer_writer, $($a), *).unwrap();
-        };
+    // the ramdisk (see build.rs) holds a PSF font that replaces the built-in one, or an image
+    // that replaces the splash logo
+    let ramdisk = boot_info.ramdisk_addr.as_ref().map(|&addr| {
+        // the bootloader maps the ramdisk at addr for the kernel's whole lifetime
+        unsafe { slice::from_raw_parts(addr as *const u8, boot_info.ramdisk_len as usize) }
+    });
+    let mut splash_image = None;
+    if let Some(ramdisk) = ramdisk {
+        match writer::font::load_psf(ramdisk) {
+            Ok(font) => {
+                writer::with_writer(|writer| writer.set_font(writer::Font::Psf(font)));
+            }
+            Err(PsfError::BadMagic) => splash_image = Some(ramdisk),
+            Err(error) => log::warn!("ramdisk is not a usable PSF font: {:?}", error),
+        }
     }
+    if let Err(error) = splash::show(splash_image) {
+        log::warn!("cannot show the splash image: {:?}", error);
+    }
+    gdt::init();
+    interrupts::init_idt();
+    x86_64::instructions::interrupts::int3(); // the breakpoint handler reports this and returns
+    interrupts::init_pics();
+    time::init();
+    x86_64::instructions::interrupts::enable();
+    time::sleep_ticks(1);
+    log::info!("timer interrupts arriving at {} Hz", time::frequency());
+    if !writer::enable_double_buffering() {
+        log::warn!("no heap memory for the back buffer, drawing to the framebuffer directly");
+    }
+    let apic_result = acpi::init(boot_info.rsdp_addr.as_ref().copied()).and_then(|()| apic::init());
+    if let Err(error) = apic_result {
+        log::warn!("staying on the 8259 PIC, APIC setup failed: {:?}", error);
+    }
+    match serial_result {
+        Ok(()) => serial::init_input(),
+        Err(error) => log::warn!("serial console unavailable: {:?}", error),
+    }
+    keyboard::init();
+     
+    println!("Testing testing {} and {}", 1, 4.0/2.0);
 
     print!("Hello from print {}", 25.0/5.0);
     println!("Hello from println!");
     print!("Hello from print\n");
 
-    frame_buffer_writer.change_cursor_position(100, 120);
+    writer::with_writer(|writer| writer.change_cursor_position(100, 120));
     println!("My name is Tireni");
     println!("I love Rust!");
 
-    loop {
-        hlt(); //stop x86_64 from being unnecessarily busy while looping
-    }
+    task::spawn(Task::new(shell::run())).expect("no room for the shell task");
+    task::spawn(Task::new(writer::blink_caret())).expect("no room for the caret task");
+    task::run()
 
 }
 
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
-    loop {
-        hlt();
-    }
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    panic::handle(info)
 }
 
 // #[no_mangle]
@@ -0,0 +1,37 @@
+pub mod frames;
+pub mod paging;
+pub mod vmalloc;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use bootloader_api::info::MemoryRegion;
+use spin::Once;
+use x86_64::{PhysAddr, VirtAddr};
+
+/// Virtual address at which the bootloader mapped all of physical memory
+/// (`config.mappings.physical_memory`).
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// The physical memory map handed over by the bootloader.
+static MEMORY_REGIONS: Once<&'static [MemoryRegion]> = Once::new();
+
+/// Records where the bootloader mapped physical memory and what the memory map looks like, and
+/// sets up the frame allocator, page tables and vmalloc area from them. Must run before
+/// [phys_to_virt] is used.
+pub fn init(physical_memory_offset: u64, memory_regions: &'static [MemoryRegion]) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset, Ordering::Relaxed);
+    MEMORY_REGIONS.call_once(|| memory_regions);
+    frames::init(memory_regions);
+    paging::init();
+    vmalloc::init();
+}
+
+/// The bootloader's memory map, or an empty one before [init].
+pub fn regions() -> &'static [MemoryRegion] {
+    MEMORY_REGIONS.get().copied().unwrap_or(&[])
+}
+
+/// Returns the address through which the kernel can access physical address `addr`.
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) + addr.as_u64())
+}
@@ -0,0 +1,230 @@
+//! Physical memory: which 4 KiB frames of RAM are free, tracked in a bitmap built from the
+//! bootloader's memory map.
+
+use core::ops::Range;
+use core::slice;
+
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use spin::{Mutex, Once};
+use x86_64::instructions::interrupts;
+use x86_64::structures::paging::frame::PhysFrameRange;
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB};
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::paging::translate;
+use super::phys_to_virt;
+
+pub const FRAME_SIZE: u64 = 4096;
+
+/// Memory below 1 MiB is never handed out: it holds firmware data on BIOS machines, and frame
+/// 0 would look like a null pointer.
+const LOW_MEMORY_END: u64 = 0x10_0000;
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// Frame counts of a [BitmapFrameAllocator].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames of usable RAM in the memory map.
+    pub total: usize,
+    /// Frames allocated or reserved, including the ones holding the bitmap.
+    pub used: usize,
+    pub free: usize,
+}
+
+/// Hands out 4 KiB frames of physical memory, one bit per frame.
+pub struct BitmapFrameAllocator {
+    /// One bit per frame from address 0 to the end of the highest usable region. A set bit
+    /// means the frame is in use or is not usable RAM.
+    bitmap: &'static mut [u64],
+    total: usize,
+    free: usize,
+    /// Index of the bitmap word to start looking for a free frame in.
+    next_word: usize,
+}
+
+impl BitmapFrameAllocator {
+    /// Builds the allocator from `regions`, storing the bitmap in the first usable region large
+    /// enough for it. Returns `None` if there is no usable memory or no room for the bitmap.
+    ///
+    /// # Safety
+    /// The usable regions of `regions` must be unused and reachable through [phys_to_virt], and
+    /// this must only be called once.
+    unsafe fn new(regions: &[MemoryRegion]) -> Option<Self> {
+        let usable = || {
+            regions
+                .iter()
+                .filter(|region| region.kind == MemoryRegionKind::Usable)
+                .map(|region| {
+                    let start = region.start.max(LOW_MEMORY_END).next_multiple_of(FRAME_SIZE);
+                    (start, region.end - region.end % FRAME_SIZE)
+                })
+                .filter(|(start, end)| start < end)
+        };
+        let frame_count = usable().map(|(_, end)| end / FRAME_SIZE).max()? as usize;
+        let words = frame_count.div_ceil(BITS_PER_WORD);
+        let bitmap_size = (words * size_of::<u64>()) as u64;
+        let (bitmap_start, _) = usable().find(|(start, end)| end - start >= bitmap_size)?;
+        let bitmap_address = phys_to_virt(PhysAddr::new(bitmap_start));
+        let bitmap = slice::from_raw_parts_mut(bitmap_address.as_mut_ptr::<u64>(), words);
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = Self {
+            bitmap,
+            total: 0,
+            free: 0,
+            next_word: 0,
+        };
+        for (start, end) in usable() {
+            for frame in start / FRAME_SIZE..end / FRAME_SIZE {
+                allocator.set_used(frame as usize, false);
+            }
+        }
+        allocator.total = allocator.free;
+        allocator.reserve(PhysAddr::new(bitmap_start)..PhysAddr::new(bitmap_start + bitmap_size));
+        Some(allocator)
+    }
+
+    fn is_used(&self, frame: usize) -> bool {
+        self.bitmap[frame / BITS_PER_WORD] & (1 << (frame % BITS_PER_WORD)) != 0
+    }
+
+    /// Marks `frame` as used or free and keeps the free count up to date.
+    fn set_used(&mut self, frame: usize, used: bool) {
+        let (word, bit) = (frame / BITS_PER_WORD, 1 << (frame % BITS_PER_WORD));
+        if used {
+            self.bitmap[word] |= bit;
+            self.free -= 1;
+        } else {
+            self.bitmap[word] &= !bit;
+            self.free += 1;
+            self.next_word = self.next_word.min(word);
+        }
+    }
+
+    fn frame_count(&self) -> usize {
+        self.bitmap.len() * BITS_PER_WORD
+    }
+
+    /// Index of the frame containing `addr`.
+    fn index(addr: PhysAddr) -> usize {
+        (addr.as_u64() / FRAME_SIZE) as usize
+    }
+
+    /// Keeps the frames overlapping `range` from being handed out. Frames that are not usable
+    /// RAM or already in use are left alone.
+    pub fn reserve(&mut self, range: Range<PhysAddr>) {
+        let end = Self::index(range.end.align_up(FRAME_SIZE)).min(self.frame_count());
+        for frame in Self::index(range.start)..end {
+            if !self.is_used(frame) {
+                self.set_used(frame, true);
+            }
+        }
+    }
+
+    /// Allocates the lowest free frame.
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        let word = (self.next_word..self.bitmap.len()).find(|&word| self.bitmap[word] != u64::MAX)?;
+        self.next_word = word;
+        let frame = word * BITS_PER_WORD + self.bitmap[word].trailing_ones() as usize;
+        self.set_used(frame, true);
+        Some(frame_at(frame))
+    }
+
+    /// Allocates `count` physically contiguous frames, for devices that access memory directly.
+    #[allow(dead_code)]
+    pub fn allocate_contiguous(&mut self, count: usize) -> Option<PhysFrameRange> {
+        if count == 0 {
+            return None;
+        }
+        let mut run_start = 0;
+        for frame in 0..self.frame_count() {
+            if self.is_used(frame) {
+                run_start = frame + 1;
+            } else if frame + 1 - run_start == count {
+                for frame in run_start..=frame {
+                    self.set_used(frame, true);
+                }
+                return Some(PhysFrame::range(frame_at(run_start), frame_at(frame + 1)));
+            }
+        }
+        None
+    }
+
+    /// Returns `frame` to the free frames.
+    ///
+    /// # Panics
+    /// If `frame` is not in use, which means it was freed twice or never allocated.
+    pub fn free(&mut self, frame: PhysFrame) {
+        let index = Self::index(frame.start_address());
+        assert!(
+            index < self.frame_count() && self.is_used(index),
+            "freeing {:?}, which is not in use",
+            frame
+        );
+        self.set_used(index, false);
+    }
+
+    /// Returns frames from [BitmapFrameAllocator::allocate_contiguous] to the free frames.
+    #[allow(dead_code)]
+    pub fn free_contiguous(&mut self, frames: PhysFrameRange) {
+        for frame in frames {
+            self.free(frame);
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.total,
+            used: self.total - self.free,
+            free: self.free,
+        }
+    }
+}
+
+fn frame_at(index: usize) -> PhysFrame {
+    PhysFrame::containing_address(PhysAddr::new(index as u64 * FRAME_SIZE))
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        self.allocate()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        self.free(frame);
+    }
+}
+
+static FRAME_ALLOCATOR: Once<Mutex<BitmapFrameAllocator>> = Once::new();
+
+/// Builds the frame allocator from the bootloader's memory map. Called by [super::init].
+pub(super) fn init(regions: &[MemoryRegion]) {
+    FRAME_ALLOCATOR.call_once(|| {
+        // the bootloader marks everything it still uses as MemoryRegionKind::Bootloader
+        let allocator = unsafe { BitmapFrameAllocator::new(regions) };
+        Mutex::new(allocator.expect("no usable memory for the frame bitmap"))
+    });
+}
+
+/// Runs `f` with exclusive access to the frame allocator, with interrupts disabled so that
+/// handlers can allocate too. Returns `None` before [init].
+pub fn with_frame_allocator<R>(f: impl FnOnce(&mut BitmapFrameAllocator) -> R) -> Option<R> {
+    let allocator = FRAME_ALLOCATOR.get()?;
+    Some(interrupts::without_interrupts(|| f(&mut allocator.lock())))
+}
+
+/// Reserves the frames behind the `size` bytes mapped at `start`, such as the framebuffer,
+/// which the memory map may list as usable RAM.
+pub fn reserve_mapped(start: VirtAddr, size: u64) {
+    with_frame_allocator(|allocator| {
+        let (first, end) = (start.align_down(FRAME_SIZE), (start + size).align_up(FRAME_SIZE));
+        for page in (first.as_u64()..end.as_u64()).step_by(FRAME_SIZE as usize) {
+            if let Some(frame) = translate(VirtAddr::new(page)) {
+                allocator.reserve(frame..frame + FRAME_SIZE);
+            }
+        }
+    });
+}
@@ -0,0 +1,245 @@
+//! The kernel's page tables, reached through the bootloader's mapping of all physical memory.
+
+use core::ops::Range;
+
+use spin::{Mutex, Once};
+use x86_64::instructions::{interrupts, tlb};
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::mapper::{FlagUpdateError, MapToError, UnmapError};
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{
+    Mapper, OffsetPageTable, Page, PageSize, PageTable, PageTableFlags, PhysFrame,
+};
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::frames::{self, BitmapFrameAllocator};
+use super::phys_to_virt;
+use crate::registry::Registry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagingError {
+    /// No free frame for the page or for a page table on the way to it.
+    OutOfFrames,
+    /// The page is mapped already.
+    AlreadyMapped,
+    /// The page is not mapped.
+    NotMapped,
+    /// The page lies inside a huge page, which this module does not split.
+    HugePage,
+    /// The vmalloc area has no free range that large left.
+    OutOfAddressSpace,
+}
+
+impl<S: PageSize> From<MapToError<S>> for PagingError {
+    fn from(error: MapToError<S>) -> Self {
+        match error {
+            MapToError::FrameAllocationFailed => PagingError::OutOfFrames,
+            MapToError::ParentEntryHugePage => PagingError::HugePage,
+            MapToError::PageAlreadyMapped(_) => PagingError::AlreadyMapped,
+        }
+    }
+}
+
+impl From<UnmapError> for PagingError {
+    fn from(error: UnmapError) -> Self {
+        match error {
+            UnmapError::ParentEntryHugePage => PagingError::HugePage,
+            UnmapError::PageNotMapped | UnmapError::InvalidFrameAddress(_) => {
+                PagingError::NotMapped
+            }
+        }
+    }
+}
+
+impl From<FlagUpdateError> for PagingError {
+    fn from(error: FlagUpdateError) -> Self {
+        match error {
+            FlagUpdateError::ParentEntryHugePage => PagingError::HugePage,
+            FlagUpdateError::PageNotMapped => PagingError::NotMapped,
+        }
+    }
+}
+
+/// Called after the kernel changed or removed the mappings of some pages, to invalidate them in
+/// the TLBs of other processors. The current processor's TLB has been flushed already.
+pub type ShootdownHook = fn(Range<VirtAddr>);
+
+/// Maximum number of hooks that can be registered at the same time.
+const MAX_SHOOTDOWN_HOOKS: usize = 4;
+
+static SHOOTDOWN_HOOKS: Registry<ShootdownHook, MAX_SHOOTDOWN_HOOKS> = Registry::new();
+
+/// Runs `hook` whenever mappings change.
+#[allow(dead_code)]
+pub fn register_shootdown_hook(hook: ShootdownHook) -> Result<(), ShootdownHook> {
+    SHOOTDOWN_HOOKS.register(hook)
+}
+
+/// Flushes `pages` from this processor's TLB and has the hooks do the same for the others.
+fn shootdown(pages: PageRange) {
+    for page in pages {
+        tlb::flush(page.start_address());
+    }
+    for hook in SHOOTDOWN_HOOKS.items() {
+        hook(pages.start.start_address()..pages.end.start_address());
+    }
+}
+
+static PAGE_TABLE: Once<Mutex<OffsetPageTable<'static>>> = Once::new();
+
+/// Takes over the active page tables. Called by [super::init] once the physical memory offset
+/// is known.
+pub(super) fn init() {
+    PAGE_TABLE.call_once(|| {
+        let level_4_table = phys_to_virt(Cr3::read().0.start_address());
+        // the bootloader's level 4 table stays active, and only this mapper changes it from now on
+        let table = unsafe { &mut *level_4_table.as_mut_ptr::<PageTable>() };
+        Mutex::new(unsafe { OffsetPageTable::new(table, phys_to_virt(PhysAddr::new(0))) })
+    });
+}
+
+/// Runs `f` with the page table and the frame allocator locked, interrupts disabled.
+fn with_tables<R>(
+    f: impl FnOnce(&mut OffsetPageTable<'static>, &mut BitmapFrameAllocator) -> R,
+) -> R {
+    let table = PAGE_TABLE.get().expect("paging used before memory::init");
+    interrupts::without_interrupts(|| {
+        let mut table = table.lock();
+        frames::with_frame_allocator(|frames| f(&mut table, frames))
+            .expect("paging used before memory::init")
+    })
+}
+
+/// Maps `page` to `frame`. Page tables needed on the way are allocated as well.
+///
+/// # Safety
+/// `frame` must not be in use for anything else unless sharing it is intended, and mapping
+/// `page` must not make existing references to it point elsewhere.
+#[allow(dead_code)]
+pub unsafe fn map(page: Page, frame: PhysFrame, flags: PageTableFlags) -> Result<(), PagingError> {
+    with_tables(|table, frames| table.map_to(page, frame, flags, frames))?.flush();
+    Ok(())
+}
+
+/// Backs `pages` with newly allocated frames. Nothing stays mapped if it fails partway.
+///
+/// # Safety
+/// Like [map]; the pages must not be in use.
+pub unsafe fn map_range(pages: PageRange, flags: PageTableFlags) -> Result<(), PagingError> {
+    for (done, page) in pages.enumerate() {
+        let result = with_tables(|table, frames| {
+            let frame = frames.allocate().ok_or(PagingError::OutOfFrames)?;
+            match table.map_to(page, frame, flags, frames) {
+                Ok(flush) => {
+                    flush.flush();
+                    Ok(())
+                }
+                Err(error) => {
+                    frames.free(frame);
+                    Err(PagingError::from(error))
+                }
+            }
+        });
+        if let Err(error) = result {
+            let mapped = Page::range(pages.start, pages.start + done as u64);
+            unmap_range(mapped, true).expect("pages mapped just now are mapped");
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Removes the mapping of `page` and returns the frame it was mapped to.
+///
+/// # Safety
+/// Nothing may use the page anymore.
+#[allow(dead_code)]
+pub unsafe fn unmap(page: Page) -> Result<PhysFrame, PagingError> {
+    let frame = with_tables(|table, _| table.unmap(page).map(|(frame, _)| frame))?;
+    shootdown(Page::range(page, page + 1));
+    Ok(frame)
+}
+
+/// Removes the mappings of `pages`, returning their frames to the frame allocator if
+/// `free_frames` is set, such as for pages mapped with [map_range].
+///
+/// # Safety
+/// Like [unmap].
+pub unsafe fn unmap_range(pages: PageRange, free_frames: bool) -> Result<(), PagingError> {
+    let result = pages.into_iter().try_for_each(|page| {
+        with_tables(|table, frames| {
+            let (frame, _) = table.unmap(page)?;
+            if free_frames {
+                frames.free(frame);
+            }
+            Ok(())
+        })
+    });
+    shootdown(pages);
+    result
+}
+
+/// Replaces the flags of the mapped `page`, for example to make it read-only. `flags` should
+/// include [PageTableFlags::PRESENT].
+///
+/// # Safety
+/// Existing references to the page must stay valid under the new flags.
+#[allow(dead_code)]
+pub unsafe fn protect(page: Page, flags: PageTableFlags) -> Result<(), PagingError> {
+    protect_range(Page::range(page, page + 1), flags)
+}
+
+/// Like [protect], for every page of `pages`.
+///
+/// # Safety
+/// Like [protect].
+#[allow(dead_code)]
+pub unsafe fn protect_range(pages: PageRange, flags: PageTableFlags) -> Result<(), PagingError> {
+    let result = pages.into_iter().try_for_each(|page| {
+        with_tables(|table, _| table.update_flags(page, flags).map(|flush| flush.ignore()))
+    });
+    shootdown(pages);
+    result.map_err(PagingError::from)
+}
+
+/// Whether `addr` is mapped in the active page tables, so that reading it cannot fault. Always
+/// false before [init], when the page tables cannot be reached yet.
+pub fn is_mapped(addr: VirtAddr) -> bool {
+    translate(addr).is_some()
+}
+
+/// Returns the physical address `addr` is mapped to in the active page tables, or `None` if it
+/// is not mapped or [init] has not run yet. Reads the tables without locking them, so that it
+/// also works in the panic handler.
+pub fn translate(addr: VirtAddr) -> Option<PhysAddr> {
+    PAGE_TABLE.get()?;
+    let mut table_addr = Cr3::read().0.start_address();
+    let indices = [addr.p4_index(), addr.p3_index(), addr.p2_index(), addr.p1_index()];
+    // bits of addr below each level's index, which address the inside of a page it maps
+    let offset_bits = [39, 30, 21, 12];
+    for (index, bits) in indices.into_iter().zip(offset_bits) {
+        let table = unsafe { &*phys_to_virt(table_addr).as_ptr::<PageTable>() };
+        let flags = table[index].flags();
+        if !flags.contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        // level 3 and level 2 entries can map a 1 GiB or 2 MiB page themselves
+        if bits == 12 || flags.contains(PageTableFlags::HUGE_PAGE) {
+            return Some(table[index].addr() + (addr.as_u64() & ((1 << bits) - 1)));
+        }
+        table_addr = table[index].addr();
+    }
+    unreachable!("level 1 entries always map a page")
+}
+
+/// Size of the address space a level 4 table entry covers.
+pub const LEVEL_4_ENTRY_SIZE: u64 = 1 << 39;
+
+/// Returns the start of an unused 512 GiB area in the upper, kernel half of the address space.
+pub(super) fn unused_level_4_entry() -> Option<VirtAddr> {
+    let table = unsafe { &*phys_to_virt(Cr3::read().0.start_address()).as_ptr::<PageTable>() };
+    // the last entry is left alone, its area would end past the top of the address space
+    let index = (256..511).find(|&index| table[index].is_unused())?;
+    // the upper half starts with sign extension of bit 47
+    Some(VirtAddr::new_truncate(index as u64 * LEVEL_4_ENTRY_SIZE))
+}
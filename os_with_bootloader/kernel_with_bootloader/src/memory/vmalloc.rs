@@ -0,0 +1,111 @@
+//! Kernel address space for memory that needs no particular address: [vmalloc] maps fresh frames,
+//! which need not be contiguous, into an otherwise unused part of the address space.
+
+use core::ops::Range;
+
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::structures::paging::{Page, PageTableFlags, Size4KiB};
+use x86_64::VirtAddr;
+
+use super::frames::FRAME_SIZE;
+use super::paging::{self, PagingError, LEVEL_4_ENTRY_SIZE};
+
+/// Most separate free ranges that are tracked. A freed range that would need another one is
+/// leaked.
+const MAX_FREE_RANGES: usize = 64;
+
+/// The free parts of the vmalloc area.
+struct AddressSpace {
+    free: [Option<Range<u64>>; MAX_FREE_RANGES],
+}
+
+impl AddressSpace {
+    /// Takes `size` bytes from the first free range large enough.
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        let range = self.free.iter_mut().flatten().find(|range| range.end - range.start >= size)?;
+        let start = range.start;
+        range.start += size;
+        Some(start)
+    }
+
+    /// Returns `range` to the free ranges, merging it with its neighbours.
+    fn release(&mut self, mut range: Range<u64>) {
+        for slot in &mut self.free {
+            match slot {
+                Some(free) if free.end == range.start => range.start = free.start,
+                Some(free) if free.start == range.end => range.end = free.end,
+                _ => continue,
+            }
+            *slot = None;
+        }
+        match self.free.iter_mut().find(|slot| slot.as_ref().is_none_or(Range::is_empty)) {
+            Some(slot) => *slot = Some(range),
+            None => log::warn!("vmalloc area too fragmented, leaking {:#x?}", range),
+        }
+    }
+
+    /// Free bytes, and the size of the largest free range.
+    fn free_space(&self) -> (u64, u64) {
+        let sizes = self.free.iter().flatten().map(|range| range.end - range.start);
+        (sizes.clone().sum(), sizes.max().unwrap_or(0))
+    }
+}
+
+static AREA: Mutex<AddressSpace> = Mutex::new(AddressSpace {
+    free: [const { None }; MAX_FREE_RANGES],
+});
+
+/// Claims an unused 512 GiB of the kernel half of the address space. Called by [super::init].
+pub(super) fn init() {
+    match paging::unused_level_4_entry() {
+        Some(start) => {
+            let start = start.as_u64();
+            AREA.lock().free[0] = Some(start..start + LEVEL_4_ENTRY_SIZE);
+        }
+        None => log::warn!("no room in the address space for vmalloc"),
+    }
+}
+
+/// Maps `size` bytes of newly allocated memory, rounded up to whole pages, with `flags` (present
+/// is implied) and returns where.
+/// An unmapped guard page follows, so that running off the end faults instead of corrupting
+/// the next allocation.
+pub fn vmalloc(size: usize, flags: PageTableFlags) -> Result<VirtAddr, PagingError> {
+    let pages = (size as u64).div_ceil(FRAME_SIZE).max(1);
+    let start = interrupts::without_interrupts(|| AREA.lock().allocate((pages + 1) * FRAME_SIZE))
+        .ok_or(PagingError::OutOfAddressSpace)?;
+    let first: Page<Size4KiB> = Page::containing_address(VirtAddr::new(start));
+    // nothing else uses addresses from the vmalloc area
+    let flags = flags | PageTableFlags::PRESENT;
+    if let Err(error) = unsafe { paging::map_range(Page::range(first, first + pages), flags) } {
+        release(start, pages);
+        return Err(error);
+    }
+    Ok(first.start_address())
+}
+
+/// Unmaps memory returned by [vmalloc] and frees the frames behind it.
+///
+/// # Safety
+/// `start` and `size` must be those of a [vmalloc] call, and nothing may use the memory anymore.
+#[allow(dead_code)]
+pub unsafe fn vfree(start: VirtAddr, size: usize) {
+    let pages = (size as u64).div_ceil(FRAME_SIZE).max(1);
+    let first = Page::containing_address(start);
+    paging::unmap_range(Page::range(first, first + pages), true)
+        .expect("vfree of memory that vmalloc did not map");
+    release(start.as_u64(), pages);
+}
+
+fn release(start: u64, pages: u64) {
+    let size = (pages + 1) * FRAME_SIZE;
+    interrupts::without_interrupts(|| AREA.lock().release(start..start + size));
+}
+
+/// Free bytes in the vmalloc area, and the largest allocation that still fits.
+pub fn free_space() -> (u64, u64) {
+    let (free, largest) = interrupts::without_interrupts(|| AREA.lock().free_space());
+    // the guard page comes out of every allocation
+    (free, largest.saturating_sub(FRAME_SIZE))
+}
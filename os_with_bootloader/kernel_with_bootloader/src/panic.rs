@@ -0,0 +1,119 @@
+//! The panic handler: reports the panic on a red screen and on the serial console, then halts.
+
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use x86_64::instructions::{self, hlt};
+use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+use x86_64::registers::rflags;
+
+use crate::backtrace::{self, Frames};
+use crate::console::{self, SinkWriter};
+use crate::graphics::Color;
+use crate::{serial, writer};
+
+const PANIC_BACKGROUND: Color = Color::new(170, 0, 0);
+const PANIC_FOREGROUND: Color = Color::WHITE;
+
+/// Number of panics so far. Any after the first happened while reporting it.
+static PANICS: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers worth knowing about when the kernel panics, read in the panic handler.
+struct Registers {
+    rsp: u64,
+    rbp: u64,
+    rflags: u64,
+    cr0: u64,
+    cr2: u64,
+    cr3: u64,
+    cr4: u64,
+}
+
+impl Registers {
+    fn read() -> Self {
+        let (rsp, rbp): (u64, u64);
+        unsafe {
+            asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags));
+            asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+        }
+        Self {
+            rsp,
+            rbp,
+            rflags: rflags::read_raw(),
+            cr0: Cr0::read_raw(),
+            cr2: Cr2::read().as_u64(),
+            cr3: Cr3::read().0.start_address().as_u64(),
+            cr4: Cr4::read_raw(),
+        }
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "RSP    {:#018x}  RBP {:#018x}", self.rsp, self.rbp)?;
+        writeln!(f, "RFLAGS {:#018x}  CR0 {:#018x}", self.rflags, self.cr0)?;
+        writeln!(f, "CR2    {:#018x}  CR3 {:#018x}", self.cr2, self.cr3)?;
+        writeln!(f, "CR4    {:#018x}", self.cr4)
+    }
+}
+
+/// Reports `info` on every output there is and stops the CPU.
+pub fn handle(info: &PanicInfo) -> ! {
+    instructions::interrupts::disable();
+    let registers = Registers::read();
+    let frames = Frames::here();
+    // The code that panicked never runs again, so locks it held can be taken over. Nothing
+    // else can hold them: interrupts are off and there is only one core.
+    unsafe {
+        serial::force_unlock();
+        console::force_unlock();
+    }
+    match PANICS.fetch_add(1, Ordering::SeqCst) {
+        0 => {
+            let shown = writer::with_writer_forced(|writer| {
+                writer.set_foreground(PANIC_FOREGROUND);
+                writer.set_background(PANIC_BACKGROUND);
+                writer.set_caret_visible(false);
+                writer.clear();
+                // the console mirrors this to the serial port
+                let _ = report(writer, info, &registers, frames);
+                writer.flush();
+            });
+            if shown.is_none() {
+                let _ = report(&mut SinkWriter, info, &registers, frames);
+            }
+        }
+        // drawing the panic screen panicked, so leave the framebuffer alone
+        1 => {
+            let _ = writeln!(SinkWriter, "\npanicked while panicking: {}", info.message());
+            if let Some(location) = info.location() {
+                let _ = writeln!(SinkWriter, "at {}", location);
+            }
+        }
+        // even the serial port panics, give up
+        _ => {}
+    }
+    loop {
+        hlt();
+    }
+}
+
+fn report(
+    out: &mut impl Write,
+    info: &PanicInfo,
+    registers: &Registers,
+    frames: Frames,
+) -> fmt::Result {
+    writeln!(out, "KERNEL PANIC")?;
+    writeln!(out)?;
+    writeln!(out, "{}", info.message())?;
+    if let Some(location) = info.location() {
+        writeln!(out, "at {}", location)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{}", registers)?;
+    writeln!(out, "Backtrace:")?;
+    backtrace::write(out, frames)
+}
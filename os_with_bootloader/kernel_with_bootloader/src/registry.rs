@@ -0,0 +1,47 @@
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// A fixed number of slots that other subsystems register hooks, sinks and the like in. The
+/// slots are only locked with interrupts disabled, so interrupt handlers can read them.
+pub struct Registry<T: Copy, const N: usize> {
+    slots: Mutex<[Option<T>; N]>,
+}
+
+impl<T: Copy, const N: usize> Registry<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: Mutex::new([None; N]),
+        }
+    }
+
+    /// Puts `item` in the first free slot, or hands it back if there is none.
+    pub fn register(&self, item: T) -> Result<(), T> {
+        interrupts::without_interrupts(|| {
+            let mut slots = self.slots.lock();
+            match slots.iter_mut().find(|slot| slot.is_none()) {
+                Some(slot) => {
+                    *slot = Some(item);
+                    Ok(())
+                }
+                None => Err(item),
+            }
+        })
+    }
+
+    /// The registered items, in the order they were added. They are copied out first, so that
+    /// an item may print or register others while the caller goes through them.
+    pub fn items(&self) -> impl Iterator<Item = T> {
+        let slots = interrupts::without_interrupts(|| *self.slots.lock());
+        slots.into_iter().flatten()
+    }
+
+    /// Releases the slots if the code that panicked was holding them.
+    ///
+    /// # Safety
+    /// Same as [crate::serial::force_unlock].
+    pub unsafe fn force_unlock(&self) {
+        if self.slots.is_locked() {
+            self.slots.force_unlock();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use bootloader_api::info::FrameBufferInfo;
+
+/// Most separate areas tracked as dirty; further ones are merged into the closest one.
+const MAX_DIRTY_RECTS: usize = 16;
+
+/// An area of the screen in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The smallest rectangle containing both.
+    fn union(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Whether the rectangles overlap or share an edge.
+    fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right()
+            && other.x <= self.right()
+            && self.y <= other.bottom()
+            && other.y <= self.bottom()
+    }
+
+    /// The part of the rectangle inside `width` × `height`.
+    pub fn clipped(&self, width: usize, height: usize) -> Rect {
+        let (x, y) = (self.x.min(width), self.y.min(height));
+        Rect::new(x, y, self.right().min(width) - x, self.bottom().min(height) - y)
+    }
+}
+
+/// The framebuffer handed over by the bootloader, optionally drawn through a back buffer in
+/// normal memory. With a back buffer, drawing only touches RAM and the changed areas are copied
+/// to video memory in bulk by [Screen::flush].
+pub struct Screen {
+    front: &'static mut [u8],
+    back: Option<&'static mut [u8]>,
+    info: FrameBufferInfo,
+    dirty: [Option<Rect>; MAX_DIRTY_RECTS],
+}
+
+impl Screen {
+    pub fn new(front: &'static mut [u8], info: FrameBufferInfo) -> Self {
+        Self {
+            front,
+            back: None,
+            info,
+            dirty: [None; MAX_DIRTY_RECTS],
+        }
+    }
+
+    pub fn info(&self) -> FrameBufferInfo {
+        self.info
+    }
+
+    /// Starts drawing into `buffer` instead of video memory. Hands the buffer back if it is
+    /// smaller than the framebuffer.
+    pub fn enable_back_buffer(
+        &mut self,
+        buffer: &'static mut [u8],
+    ) -> Result<(), &'static mut [u8]> {
+        if buffer.len() < self.front.len() {
+            return Err(buffer);
+        }
+        let (buffer, _) = buffer.split_at_mut(self.front.len());
+        buffer.copy_from_slice(self.front);
+        self.back = Some(buffer);
+        Ok(())
+    }
+
+    /// The bytes to draw into. Areas changed through this must be reported to
+    /// [Screen::mark_dirty] to reach the display.
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        match &mut self.back {
+            Some(back) => back,
+            None => self.front,
+        }
+    }
+
+    /// Notes that `rect` was drawn to and needs to be copied to video memory.
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        if self.back.is_none() {
+            return;
+        }
+        let rect = rect.clipped(self.info.width, self.info.height);
+        if rect.is_empty() {
+            return;
+        }
+        for slot in self.dirty.iter_mut() {
+            match slot {
+                Some(dirty) if dirty.touches(&rect) => {
+                    *dirty = dirty.union(&rect);
+                    return;
+                }
+                None => {
+                    *slot = Some(rect);
+                    return;
+                }
+                Some(_) => {}
+            }
+        }
+        // out of slots: grow whichever rectangle grows the least
+        let closest = self.dirty.iter_mut().flatten().min_by_key(|dirty| {
+            dirty.union(&rect).area() - dirty.area()
+        });
+        if let Some(closest) = closest {
+            *closest = closest.union(&rect);
+        }
+    }
+
+    /// Marks the whole screen as changed.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = [None; MAX_DIRTY_RECTS];
+        self.mark_dirty(Rect::new(0, 0, self.info.width, self.info.height));
+    }
+
+    /// Copies the areas changed since the last flush from the back buffer to video memory, one
+    /// pixel row at a time.
+    pub fn flush(&mut self) {
+        let Some(back) = &self.back else { return };
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let row_bytes = self.info.stride * bytes_per_pixel;
+        for rect in self.dirty.iter_mut().filter_map(Option::take) {
+            for y in rect.y..rect.bottom() {
+                let start = y * row_bytes + rect.x * bytes_per_pixel;
+                let end = y * row_bytes + rect.right() * bytes_per_pixel;
+                self.front[start..end].copy_from_slice(&back[start..end]);
+            }
+        }
+    }
+}
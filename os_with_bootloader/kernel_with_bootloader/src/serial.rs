@@ -0,0 +1,256 @@
+use core::fmt::{self, Write};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_util::Stream;
+use spin::Mutex;
+use x86_64::instructions::{interrupts, port::Port};
+
+use crate::interrupts::set_irq_handler;
+use crate::task::InputQueue;
+
+/// I/O base of the first legacy serial port.
+pub const COM1_BASE: u16 = 0x3F8;
+/// I/O base of the second legacy serial port.
+pub const COM2_BASE: u16 = 0x2F8;
+
+/// Baud rate used for the kernel console. `-serial stdio` in QEMU accepts any rate.
+pub const CONSOLE_BAUD: u32 = 115_200;
+
+/// IRQ line of COM1.
+const COM1_IRQ: u8 = 4;
+
+/// Number of bytes received on COM1 that are buffered until they are read; further bytes are
+/// dropped.
+const RECEIVE_QUEUE_SIZE: usize = 256;
+
+/// Clock of the 16550 divided by 16; the divisor latch is programmed as `UART_CLOCK / baud`.
+const UART_CLOCK: u32 = 115_200;
+
+// Register offsets from the port base.
+const DATA: u16 = 0; // receive/transmit buffer, divisor low byte while DLAB is set
+const INTERRUPT_ENABLE: u16 = 1; // divisor high byte while DLAB is set
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const IER_RECEIVED_DATA: u8 = 1 << 0;
+
+const LCR_DLAB: u8 = 1 << 7;
+const LCR_8N1: u8 = 0b0000_0011;
+/// Enable and clear both FIFOs, interrupt once 14 bytes are queued.
+const FCR_ENABLE_CLEAR_14: u8 = 0b1100_0111;
+/// DTR, RTS and OUT2 (OUT2 gates the IRQ line on PC hardware).
+const MCR_NORMAL: u8 = 0b0000_1011;
+const MCR_LOOPBACK: u8 = 0b0001_1110;
+
+/// Snapshot of the line status register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineStatus(u8);
+
+impl LineStatus {
+    /// A received byte is waiting in the receive buffer.
+    pub fn data_ready(self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    pub fn parity_error(self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    pub fn framing_error(self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// The transmit holding register can accept another byte.
+    pub fn transmit_empty(self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// The baud rate cannot be produced by an integer divisor of the UART clock.
+    InvalidBaudRate(u32),
+    /// Nothing answered the loopback test, so there is no UART at this address.
+    NotPresent,
+}
+
+/// A 16550-compatible UART driven through port I/O.
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    /// Creates a driver for the UART at `base`. Call [SerialPort::init] before use.
+    ///
+    /// # Safety
+    /// `base` must be the I/O base of a 16550 UART that nothing else drives.
+    pub const unsafe fn new(base: u16) -> Self {
+        Self { base }
+    }
+
+    fn read_reg(&self, offset: u16) -> u8 {
+        unsafe { Port::new(self.base + offset).read() }
+    }
+
+    fn write_reg(&mut self, offset: u16, value: u8) {
+        unsafe { Port::new(self.base + offset).write(value) }
+    }
+
+    /// Programs the port for `baud` 8N1 with FIFOs enabled and checks that it is present.
+    pub fn init(&mut self, baud: u32) -> Result<(), SerialError> {
+        let divisor = UART_CLOCK
+            .checked_div(baud)
+            .filter(|&divisor| divisor * baud == UART_CLOCK)
+            .and_then(|divisor| u16::try_from(divisor).ok())
+            .ok_or(SerialError::InvalidBaudRate(baud))?;
+
+        self.write_reg(INTERRUPT_ENABLE, 0);
+        self.write_reg(LINE_CONTROL, LCR_DLAB);
+        self.write_reg(DATA, divisor as u8);
+        self.write_reg(INTERRUPT_ENABLE, (divisor >> 8) as u8);
+        self.write_reg(LINE_CONTROL, LCR_8N1);
+        self.write_reg(FIFO_CONTROL, FCR_ENABLE_CLEAR_14);
+
+        // Echo a byte through the chip in loopback mode to see whether it exists.
+        self.write_reg(MODEM_CONTROL, MCR_LOOPBACK);
+        self.write_reg(DATA, 0xAE);
+        if self.read_reg(DATA) != 0xAE {
+            return Err(SerialError::NotPresent);
+        }
+        self.write_reg(MODEM_CONTROL, MCR_NORMAL);
+
+        // throw away whatever arrived before we were listening
+        while self.try_receive().is_some() {}
+        Ok(())
+    }
+
+    /// Raises the port's IRQ whenever a byte has been received.
+    pub fn enable_receive_interrupt(&mut self) {
+        self.write_reg(INTERRUPT_ENABLE, IER_RECEIVED_DATA);
+    }
+
+    pub fn line_status(&self) -> LineStatus {
+        LineStatus(self.read_reg(LINE_STATUS))
+    }
+
+    /// Sends one byte, polling until the transmitter has room for it.
+    pub fn send(&mut self, byte: u8) {
+        while !self.line_status().transmit_empty() {
+            core::hint::spin_loop();
+        }
+        self.write_reg(DATA, byte);
+    }
+
+    /// Returns the next received byte, if one is waiting. Bytes that arrived with a parity or
+    /// framing error are dropped.
+    pub fn try_receive(&mut self) -> Option<u8> {
+        loop {
+            let status = self.line_status();
+            if !status.data_ready() {
+                return None;
+            }
+            let byte = self.read_reg(DATA);
+            if !status.parity_error() && !status.framing_error() {
+                return Some(byte);
+            }
+        }
+    }
+}
+
+impl Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // terminals expect CRLF line endings
+            if byte == b'\n' {
+                self.send(b'\r');
+            }
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+pub static COM1: Mutex<SerialPort> = Mutex::new(unsafe { SerialPort::new(COM1_BASE) });
+pub static COM2: Mutex<SerialPort> = Mutex::new(unsafe { SerialPort::new(COM2_BASE) });
+
+static RECEIVED: InputQueue<u8, RECEIVE_QUEUE_SIZE> = InputQueue::new();
+
+/// Sets up COM1 as a console and mirrors everything printed on the framebuffer to it.
+/// COM2 is initialised as well but left to its users.
+pub fn init() -> Result<(), SerialError> {
+    let _ = COM2.lock().init(CONSOLE_BAUD);
+    COM1.lock().init(CONSOLE_BAUD)?;
+    let _ = crate::console::register_sink(console_sink);
+    Ok(())
+}
+
+/// Starts passing the bytes received on COM1 to [SerialInput]. The interrupt controllers must be
+/// set up.
+pub fn init_input() {
+    set_irq_handler(COM1_IRQ, on_interrupt);
+    interrupts::without_interrupts(|| COM1.lock().enable_receive_interrupt());
+}
+
+fn on_interrupt() {
+    let mut port = COM1.lock();
+    while let Some(byte) = port.try_receive() {
+        // a full queue means nobody is reading input
+        let _ = RECEIVED.push(byte);
+    }
+}
+
+/// Bytes received on COM1, once [init_input] has run. Only one task may read them.
+pub struct SerialInput(());
+
+pub fn input() -> SerialInput {
+    SerialInput(())
+}
+
+impl Stream for SerialInput {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<u8>> {
+        RECEIVED.poll_pop(context).map(Some)
+    }
+}
+
+/// Releases the ports if the code that panicked was holding them.
+///
+/// # Safety
+/// Only for the panic handler, which runs with interrupts disabled and never returns to the
+/// code that held the lock.
+pub unsafe fn force_unlock() {
+    for port in [&COM1, &COM2] {
+        if port.is_locked() {
+            port.force_unlock();
+        }
+    }
+}
+
+fn console_sink(s: &str) {
+    // the receive interrupt takes the port as well
+    interrupts::without_interrupts(|| {
+        let _ = COM1.lock().write_str(s);
+    });
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    interrupts::without_interrupts(|| {
+        let _ = COM1.lock().write_fmt(args);
+    });
+}
@@ -0,0 +1,268 @@
+mod commands;
+mod input;
+mod line;
+
+use core::fmt::Write;
+use core::future;
+use core::iter;
+use core::task::Poll;
+
+use futures_util::StreamExt;
+
+use crate::keyboard::{self, KeyEvents, KeyState};
+use crate::registry::Registry;
+use crate::serial::{self, SerialInput};
+use crate::writer::{self, constants::font_constants::BACKSPACE};
+use crate::{print, println};
+use input::{Key, TerminalDecoder};
+use line::{History, Line, MAX_LINE};
+
+const PROMPT: &str = "> ";
+
+/// Most words a command line may consist of, including the command name.
+const MAX_ARGS: usize = 16;
+
+/// Maximum number of commands other subsystems can add with [register_command].
+const MAX_REGISTERED_COMMANDS: usize = 16;
+
+/// A command the shell can run.
+#[derive(Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    /// How to call the command, shown by `help`.
+    pub usage: &'static str,
+    /// What the command does, shown by `help`.
+    pub help: &'static str,
+    /// Runs the command with the words that followed its name. An error is printed after the
+    /// command name.
+    pub run: fn(args: &[&str]) -> Result<(), &'static str>,
+}
+
+static REGISTERED_COMMANDS: Registry<Command, MAX_REGISTERED_COMMANDS> = Registry::new();
+
+/// Makes `command` available in the shell. Built-in commands take precedence over registered
+/// ones with the same name.
+#[allow(dead_code)]
+pub fn register_command(command: Command) -> Result<(), Command> {
+    REGISTERED_COMMANDS.register(command)
+}
+
+/// Built-in commands followed by registered ones.
+fn commands() -> impl Iterator<Item = Command> {
+    commands::BUILTINS.iter().copied().chain(REGISTERED_COMMANDS.items())
+}
+
+/// Reads and runs commands typed on the keyboard or on a terminal on COM1, on the shell console,
+/// which it brings to the screen. Runs as a task forever, mostly waiting for keys.
+pub async fn run() {
+    writer::with_writer(|writer| {
+        writer.switch_to(writer::SHELL_CONSOLE);
+        writer.set_caret_visible(true);
+    });
+    let mut editor = Editor::new();
+    loop {
+        print!("{}", PROMPT);
+        let line = editor.read_line().await;
+        execute(&line);
+    }
+}
+
+fn execute(line: &Line) {
+    let mut buffer = [0; MAX_LINE * 4];
+    let mut words = line.encode(&mut buffer).split_whitespace();
+    let mut args = [""; MAX_ARGS];
+    let mut count = 0;
+    for word in words.by_ref().take(MAX_ARGS) {
+        args[count] = word;
+        count += 1;
+    }
+    let Some((&name, args)) = args[..count].split_first() else { return };
+    if words.next().is_some() {
+        println!("{}: more than {} arguments", name, MAX_ARGS - 1);
+        return;
+    }
+    match commands().find(|command| command.name == name) {
+        Some(command) => {
+            if let Err(error) = (command.run)(args) {
+                println!("{}: {}", name, error);
+            }
+        }
+        None => println!("unknown command: {} (try `help`)", name),
+    }
+}
+
+/// Writes `text` to the console.
+fn emit(text: impl IntoIterator<Item = char>) {
+    writer::with_writer(|writer| {
+        let mut buffer = [0; 4];
+        for c in text {
+            let _ = writer.write_str(c.encode_utf8(&mut buffer));
+        }
+    });
+}
+
+/// Moves the cursor `count` characters to the left.
+fn move_back(count: usize) {
+    emit(iter::repeat_n(BACKSPACE, count));
+}
+
+/// Line editor. The screen is only updated with characters, spaces and backspaces, so that a
+/// serial terminal mirroring the console shows the same line.
+struct Editor {
+    line: Line,
+    cursor: usize,
+    history: History,
+    /// How many entries back in the history the line being edited was recalled from.
+    recalled: Option<usize>,
+    /// The new line put aside while the history is browsed.
+    draft: Line,
+    events: KeyEvents,
+    serial: SerialInput,
+    terminal: TerminalDecoder,
+}
+
+impl Editor {
+    fn new() -> Self {
+        Self {
+            line: Line::new(),
+            cursor: 0,
+            history: History::new(),
+            recalled: None,
+            draft: Line::new(),
+            events: keyboard::events(),
+            serial: serial::input(),
+            terminal: TerminalDecoder::new(),
+        }
+    }
+
+    /// Waits for the next key pressed on the keyboard or the serial terminal. Keyboard keys
+    /// typed while another console is shown are dropped.
+    async fn next_key(&mut self) -> Key {
+        future::poll_fn(|context| {
+            while let Poll::Ready(Some(event)) = self.events.poll_next_unpin(context) {
+                let shown = writer::active_console() == writer::SHELL_CONSOLE;
+                if event.state == KeyState::Down && shown {
+                    if let Some(key) = Key::from_event(event) {
+                        return Poll::Ready(key);
+                    }
+                }
+            }
+            while let Poll::Ready(Some(byte)) = self.serial.poll_next_unpin(context) {
+                if let Some(key) = self.terminal.advance(byte) {
+                    return Poll::Ready(key);
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn read_line(&mut self) -> Line {
+        self.line = Line::new();
+        self.cursor = 0;
+        self.recalled = None;
+        loop {
+            match self.next_key().await {
+                Key::Left if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    move_back(1);
+                }
+                Key::Right if self.cursor < self.line.len() => {
+                    emit([self.line.chars()[self.cursor]]);
+                    self.cursor += 1;
+                }
+                Key::Home => {
+                    move_back(self.cursor);
+                    self.cursor = 0;
+                }
+                Key::End => self.move_to_end(),
+                Key::PageUp => {
+                    writer::with_writer(|writer| writer.page_up());
+                }
+                Key::PageDown => {
+                    writer::with_writer(|writer| writer.page_down());
+                }
+                Key::Up => self.recall(self.recalled.map_or(0, |age| age + 1)),
+                Key::Down => match self.recalled {
+                    Some(0) => {
+                        self.recalled = None;
+                        self.replace_line(self.draft);
+                    }
+                    Some(age) => self.recall(age - 1),
+                    None => {}
+                },
+                Key::Delete if self.cursor < self.line.len() => {
+                    self.line.remove(self.cursor);
+                    self.redraw_tail(1);
+                }
+                Key::Char(c) => match c {
+                    '\n' => {
+                        self.move_to_end();
+                        println!();
+                        self.history.add(&self.line);
+                        return self.line;
+                    }
+                    BACKSPACE if self.cursor > 0 => {
+                        self.cursor -= 1;
+                        self.line.remove(self.cursor);
+                        move_back(1);
+                        self.redraw_tail(1);
+                    }
+                    // Ctrl+C abandons the line
+                    '\u{3}' => {
+                        self.move_to_end();
+                        println!("^C");
+                        return Line::new();
+                    }
+                    // Ctrl+L clears the screen and starts over at the top
+                    '\u{c}' => {
+                        writer::with_writer(|writer| writer.clear());
+                        print!("{}", PROMPT);
+                        emit(self.line.chars().iter().copied());
+                        move_back(self.line.len() - self.cursor);
+                    }
+                    c if !c.is_control() && self.line.insert(self.cursor, c) => {
+                        self.cursor += 1;
+                        emit([c]);
+                        self.redraw_tail(0);
+                    }
+                    _ => {}
+                },
+                // at either end of the line
+                Key::Left | Key::Right | Key::Delete => {}
+            }
+        }
+    }
+
+    fn move_to_end(&mut self) {
+        emit(self.line.chars()[self.cursor..].iter().copied());
+        self.cursor = self.line.len();
+    }
+
+    /// Redraws the line from the cursor onwards, blanks out the `erased` characters that used to
+    /// follow it, and puts the cursor back.
+    fn redraw_tail(&self, erased: usize) {
+        let tail = &self.line.chars()[self.cursor..];
+        emit(tail.iter().copied().chain(iter::repeat_n(' ', erased)));
+        move_back(tail.len() + erased);
+    }
+
+    /// Replaces the line with the history entry `age` entries back, if there is one.
+    fn recall(&mut self, age: usize) {
+        let Some(&line) = self.history.get(age) else { return };
+        if self.recalled.is_none() {
+            self.draft = self.line;
+        }
+        self.recalled = Some(age);
+        self.replace_line(line);
+    }
+
+    fn replace_line(&mut self, line: Line) {
+        move_back(self.cursor);
+        let erased = self.line.len().saturating_sub(line.len());
+        self.line = line;
+        self.cursor = 0;
+        self.redraw_tail(erased);
+        self.move_to_end();
+    }
+}
@@ -0,0 +1,443 @@
+use bootloader_api::info::MemoryRegionKind;
+use noto_sans_mono_bitmap::{FontWeight, RasterHeight};
+
+use super::Command;
+use crate::keyboard::{self, Layout};
+use crate::graphics::{Point, MAX_POLYGON_POINTS};
+use crate::memory::{frames, vmalloc};
+use crate::writer::{font, Color, Font};
+use crate::{acpi, allocator, memory, print, println, time, writer};
+
+/// Commands that are always available.
+pub const BUILTINS: &[Command] = &[
+    Command {
+        name: "help",
+        usage: "help",
+        help: "list the available commands",
+        run: help,
+    },
+    Command {
+        name: "clear",
+        usage: "clear",
+        help: "clear the screen",
+        run: clear,
+    },
+    Command {
+        name: "echo",
+        usage: "echo [word...]",
+        help: "print the words",
+        run: echo,
+    },
+    Command {
+        name: "mem",
+        usage: "mem",
+        help: "summarize the physical memory map",
+        run: mem,
+    },
+    Command {
+        name: "heap",
+        usage: "heap [mark|leaks|check]",
+        help: "show heap statistics, allocations since the mark (heap_debug) or check limits",
+        run: heap,
+    },
+    Command {
+        name: "uptime",
+        usage: "uptime",
+        help: "show the time since boot",
+        run: uptime,
+    },
+    Command {
+        name: "cursor",
+        usage: "cursor <x> <y>",
+        help: "move the text cursor to pixel position x, y",
+        run: cursor,
+    },
+    Command {
+        name: "color",
+        usage: "color [fg] [bg]",
+        help: "show or set the text colors, by name or as rrggbb",
+        run: color,
+    },
+    Command {
+        name: "draw",
+        usage: "draw <shape> <color> <x y...>",
+        help: "draw a pixel, line, rect, circle, triangle (or fill...) or filled polygon",
+        run: draw,
+    },
+    Command {
+        name: "font",
+        usage: "font [size] [weight] | psf",
+        help: "show or change the console font: 14-32 and light/regular/bold, or the PSF font",
+        run: font,
+    },
+    Command {
+        name: "scrollback",
+        usage: "scrollback [lines]",
+        help: "show or change how many lines Shift+PageUp can go back",
+        run: scrollback,
+    },
+    Command {
+        name: "layout",
+        usage: "layout [us|uk|azerty]",
+        help: "show or change the keyboard layout",
+        run: layout,
+    },
+    Command {
+        name: "reboot",
+        usage: "reboot",
+        help: "restart the machine",
+        run: reboot,
+    },
+    Command {
+        name: "shutdown",
+        usage: "shutdown",
+        help: "power the machine off",
+        run: shutdown,
+    },
+];
+
+fn help(_args: &[&str]) -> Result<(), &'static str> {
+    for command in super::commands() {
+        println!("  {:<24}{}", command.usage, command.help);
+    }
+    Ok(())
+}
+
+fn clear(_args: &[&str]) -> Result<(), &'static str> {
+    writer::with_writer(|writer| writer.clear());
+    Ok(())
+}
+
+fn echo(args: &[&str]) -> Result<(), &'static str> {
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            print!(" ");
+        }
+        print!("{}", arg);
+    }
+    println!();
+    Ok(())
+}
+
+fn mem(_args: &[&str]) -> Result<(), &'static str> {
+    let regions = memory::regions();
+    if regions.is_empty() {
+        return Err("no memory map available");
+    }
+    let (mut usable, mut bootloader, mut reserved) = (0, 0, 0);
+    for region in regions {
+        let size = region.end - region.start;
+        match region.kind {
+            MemoryRegionKind::Usable => usable += size,
+            MemoryRegionKind::Bootloader => bootloader += size,
+            _ => reserved += size,
+        }
+    }
+    println!("{} regions", regions.len());
+    println!("  usable:     {:>10} KiB", usable / 1024);
+    println!("  bootloader: {:>10} KiB", bootloader / 1024);
+    println!("  reserved:   {:>10} KiB", reserved / 1024);
+    if let Some(stats) = frames::with_frame_allocator(|allocator| allocator.stats()) {
+        let kib = |frames: usize| frames as u64 * frames::FRAME_SIZE / 1024;
+        println!("frames of usable memory");
+        println!("  total:      {:>10} KiB", kib(stats.total));
+        println!("  used:       {:>10} KiB", kib(stats.used));
+        println!("  free:       {:>10} KiB", kib(stats.free));
+    }
+    let heap = allocator::stats();
+    println!("heap");
+    println!("  in use:     {:>10} KiB", heap.in_use / 1024);
+    println!("  free:       {:>10} KiB", heap.free / 1024);
+    let (free, largest) = vmalloc::free_space();
+    println!("vmalloc area");
+    println!("  free:       {:>10} MiB", free / (1024 * 1024));
+    println!("  largest:    {:>10} MiB", largest / (1024 * 1024));
+    Ok(())
+}
+
+fn heap(args: &[&str]) -> Result<(), &'static str> {
+    match args {
+        [] => {}
+        ["mark"] => return heap_mark(),
+        ["leaks"] => return heap_leaks(),
+        ["check"] => return heap_check(),
+        _ => return Err("expected nothing, mark, leaks or check"),
+    }
+    let stats = allocator::stats();
+    println!("{} allocator, {} KiB", stats.allocator, stats.size / 1024);
+    println!("  in use:     {:>10} bytes, peak {}", stats.in_use, stats.peak);
+    println!("  free:       {:>10} bytes, largest {}", stats.free, stats.largest_free);
+    println!("  fragmented: {:>10} %", stats.fragmentation());
+    println!("  allocated:  {:>10} times, {} freed", stats.allocations, stats.frees);
+    println!("  failed:     {:>10} times", stats.failures);
+    println!("live allocations by size");
+    for (class, &count) in stats.live_by_class.iter().enumerate() {
+        match allocator::size_class_limit(class) {
+            Some(limit) => println!("  <= {:<7} {:>10}", limit, count),
+            None => println!("  larger     {:>10}", count),
+        }
+    }
+    Ok(())
+}
+
+/// Asks for more memory than the address space has room for, which must fail rather than wrap
+/// around.
+fn heap_check() -> Result<(), &'static str> {
+    let layout = core::alloc::Layout::from_size_align(isize::MAX as usize - 4095, 8)
+        .expect("valid layout");
+    // a non-null result cannot be real memory, so it is not handed back
+    if !unsafe { alloc::alloc::alloc(layout) }.is_null() {
+        return Err("a huge allocation succeeded");
+    }
+    println!("a huge allocation failed, as it should");
+    Ok(())
+}
+
+#[cfg(feature = "heap_debug")]
+fn heap_mark() -> Result<(), &'static str> {
+    allocator::mark();
+    Ok(())
+}
+
+#[cfg(feature = "heap_debug")]
+fn heap_leaks() -> Result<(), &'static str> {
+    use crate::backtrace;
+
+    let unrecorded = allocator::live_allocations(|allocation| {
+        print!("  #{} {:#x}, {} bytes", allocation.sequence, allocation.address, allocation.size);
+        // the innermost callers are the allocator itself and the alloc crate
+        let callers = allocation.callers.iter().take_while(|&&address| address != 0);
+        let call_site = callers
+            .filter_map(|&address| backtrace::resolve_return_address(address))
+            .find(|(name, _)| !is_allocation_machinery(name));
+        match call_site {
+            Some((name, offset)) => println!(" from {}+{:#x}", name, offset),
+            None => println!(" from {:#x}", allocation.callers[0]),
+        }
+    });
+    if unrecorded > 0 {
+        println!("  and {} allocations there was no room to record", unrecorded);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "heap_debug")]
+fn is_allocation_machinery(name: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "alloc::",
+        "<alloc::",
+        "__rust",
+        "__rdl",
+        "kernel_with_bootloader::allocator",
+        "<kernel_with_bootloader::allocator",
+    ];
+    PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(not(feature = "heap_debug"))]
+fn heap_mark() -> Result<(), &'static str> {
+    Err("leak tracking needs the heap_debug feature")
+}
+
+#[cfg(not(feature = "heap_debug"))]
+fn heap_leaks() -> Result<(), &'static str> {
+    heap_mark()
+}
+
+fn uptime(_args: &[&str]) -> Result<(), &'static str> {
+    let uptime = time::uptime();
+    println!("up {}.{:03} s ({} ticks)", uptime.as_secs(), uptime.subsec_millis(), time::ticks());
+    Ok(())
+}
+
+fn cursor(args: &[&str]) -> Result<(), &'static str> {
+    let [x, y] = args else { return Err("expected <x> <y>") };
+    let x = x.parse().map_err(|_| "x is not a number")?;
+    let y = y.parse().map_err(|_| "y is not a number")?;
+    writer::with_writer(|writer| writer.change_cursor_position(x, y));
+    Ok(())
+}
+
+fn color(args: &[&str]) -> Result<(), &'static str> {
+    let (foreground, background) = match args {
+        [] => {
+            let colors = writer::with_writer(|writer| (writer.foreground(), writer.background()));
+            if let Some((fg, bg)) = colors {
+                println!(
+                    "fg {:02x}{:02x}{:02x}, bg {:02x}{:02x}{:02x}",
+                    fg.r, fg.g, fg.b, bg.r, bg.g, bg.b
+                );
+            }
+            return Ok(());
+        }
+        [foreground] => (parse_color(foreground)?, None),
+        [foreground, background] => (parse_color(foreground)?, Some(parse_color(background)?)),
+        _ => return Err("expected at most <fg> <bg>"),
+    };
+    writer::with_writer(|writer| {
+        writer.set_foreground(foreground);
+        if let Some(background) = background {
+            writer.set_background(background);
+        }
+    });
+    Ok(())
+}
+
+/// Names of the 16 ANSI palette colors, in palette order.
+const COLOR_NAMES: [&str; 16] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    "brightblack", "brightred", "brightgreen", "brightyellow",
+    "brightblue", "brightmagenta", "brightcyan", "brightwhite",
+];
+
+fn parse_color(arg: &str) -> Result<Color, &'static str> {
+    if let Some(index) = COLOR_NAMES.iter().position(|name| *name == arg) {
+        return Ok(Color::from_ansi_256(index as u8));
+    }
+    match u32::from_str_radix(arg.trim_start_matches('#'), 16) {
+        Ok(rgb) if arg.trim_start_matches('#').len() == 6 => {
+            Ok(Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        }
+        _ => Err("colors are names like red or brightblue, or rrggbb"),
+    }
+}
+
+fn draw(args: &[&str]) -> Result<(), &'static str> {
+    let [shape, color, numbers @ ..] = args else {
+        return Err("expected <shape> <color> <x y...>");
+    };
+    let color = parse_color(color)?;
+    let mut values = [0; 2 * MAX_POLYGON_POINTS];
+    let values = values.get_mut(..numbers.len()).ok_or("too many coordinates")?;
+    for (value, number) in values.iter_mut().zip(numbers) {
+        *value = number.parse::<isize>().map_err(|_| "coordinates must be numbers")?;
+    }
+    let point = |index: usize| Point::new(values[2 * index], values[2 * index + 1]);
+    let size = |index: usize| {
+        usize::try_from(values[index]).map_err(|_| "sizes cannot be negative")
+    };
+    writer::with_writer(|writer| {
+        let mut canvas = writer.canvas();
+        // anything further out would only take long to step through
+        let limit = 4 * canvas.width().max(canvas.height());
+        if values.iter().any(|value| value.unsigned_abs() > limit) {
+            return Err("coordinates and sizes must be within four screens of the origin");
+        }
+        match (*shape, values.len()) {
+            ("pixel", 2) => canvas.plot(point(0), color),
+            ("line", 4) => canvas.line(point(0), point(1), color),
+            ("rect", 4) => canvas.rect(point(0), size(2)?, size(3)?, color),
+            ("fillrect", 4) => canvas.fill_rect(point(0), size(2)?, size(3)?, color),
+            ("circle", 3) => canvas.circle(point(0), size(2)?, color),
+            ("fillcircle", 3) => canvas.fill_circle(point(0), size(2)?, color),
+            ("triangle", 6) => canvas.triangle([point(0), point(1), point(2)], color),
+            ("filltriangle", 6) => canvas.fill_triangle([point(0), point(1), point(2)], color),
+            ("polygon", count) if count >= 6 && count % 2 == 0 => {
+                let mut corners = [Point::new(0, 0); MAX_POLYGON_POINTS];
+                for (index, corner) in corners.iter_mut().take(count / 2).enumerate() {
+                    *corner = point(index);
+                }
+                canvas.fill_polygon(&corners[..count / 2], color);
+            }
+            _ => return Err("unknown shape or wrong number of coordinates"),
+        }
+        Ok(())
+    })
+    .unwrap_or(Ok(()))
+}
+
+fn font(args: &[&str]) -> Result<(), &'static str> {
+    let Some(current) = writer::with_writer(|writer| writer.font()) else { return Ok(()) };
+    let font = match (args, current) {
+        ([], Font::Noto { size, weight }) => {
+            println!("noto {} {}", size.val(), weight_name(weight));
+            return Ok(());
+        }
+        ([], Font::Psf(font)) => {
+            println!("psf {}x{}", font.width(), font.height());
+            return Ok(());
+        }
+        (["psf"], _) => Font::Psf(font::loaded_psf().ok_or("no PSF font was loaded at boot")?),
+        ([size], Font::Noto { weight, .. }) => Font::Noto {
+            size: parse_font_size(size)?,
+            weight,
+        },
+        ([size], Font::Psf(_)) => Font::Noto {
+            size: parse_font_size(size)?,
+            weight: FontWeight::Regular,
+        },
+        ([size, weight], _) => Font::Noto {
+            size: parse_font_size(size)?,
+            weight: parse_font_weight(weight)?,
+        },
+        _ => return Err("expected [size] [weight] or psf"),
+    };
+    writer::with_writer(|writer| writer.set_font(font));
+    Ok(())
+}
+
+fn parse_font_size(arg: &str) -> Result<RasterHeight, &'static str> {
+    match arg {
+        "14" => Ok(RasterHeight::Size14),
+        "16" => Ok(RasterHeight::Size16),
+        "20" => Ok(RasterHeight::Size20),
+        "24" => Ok(RasterHeight::Size24),
+        "32" => Ok(RasterHeight::Size32),
+        _ => Err("sizes are 14, 16, 20, 24 and 32"),
+    }
+}
+
+fn parse_font_weight(arg: &str) -> Result<FontWeight, &'static str> {
+    match arg {
+        "light" => Ok(FontWeight::Light),
+        "regular" => Ok(FontWeight::Regular),
+        "bold" => Ok(FontWeight::Bold),
+        _ => Err("weights are light, regular and bold"),
+    }
+}
+
+fn weight_name(weight: FontWeight) -> &'static str {
+    match weight {
+        FontWeight::Light => "light",
+        FontWeight::Regular => "regular",
+        FontWeight::Bold => "bold",
+    }
+}
+
+fn scrollback(args: &[&str]) -> Result<(), &'static str> {
+    let limit = match args {
+        [] => writer::with_writer(|writer| writer.scrollback_limit()),
+        [lines] => {
+            let lines = lines.parse().map_err(|_| "lines is not a number")?;
+            writer::with_writer(|writer| writer.set_scrollback_limit(lines))
+        }
+        _ => return Err("expected at most one argument"),
+    };
+    if let Some(limit) = limit {
+        println!("{} lines", limit);
+    }
+    Ok(())
+}
+
+fn layout(args: &[&str]) -> Result<(), &'static str> {
+    let layout = match args {
+        [] => {
+            println!("{:?}", keyboard::layout());
+            return Ok(());
+        }
+        ["us"] => Layout::Us,
+        ["uk"] => Layout::Uk,
+        ["azerty"] => Layout::Azerty,
+        _ => return Err("expected one of us, uk, azerty"),
+    };
+    keyboard::set_layout(layout);
+    Ok(())
+}
+
+fn reboot(_args: &[&str]) -> Result<(), &'static str> {
+    acpi::power::reboot()
+}
+
+fn shutdown(_args: &[&str]) -> Result<(), &'static str> {
+    acpi::power::shutdown()
+}
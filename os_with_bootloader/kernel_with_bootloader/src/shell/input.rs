@@ -0,0 +1,186 @@
+use crate::keyboard::{KeyCode, KeyEvent};
+use crate::writer::constants::font_constants::BACKSPACE;
+
+/// A key the line editor reacts to, pressed on the keyboard or on a serial terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Delete,
+    /// Pages back through the scrollback: Shift+Page Up on the keyboard.
+    PageUp,
+    PageDown,
+    Char(char),
+}
+
+impl Key {
+    /// The key a keyboard press stands for, if it is one the editor knows.
+    pub fn from_event(event: KeyEvent) -> Option<Key> {
+        let shift = event.modifiers.shift();
+        let key = match event.code {
+            KeyCode::ArrowLeft => Key::Left,
+            KeyCode::ArrowRight => Key::Right,
+            KeyCode::ArrowUp => Key::Up,
+            KeyCode::ArrowDown => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::PageUp if shift => Key::PageUp,
+            KeyCode::PageDown if shift => Key::PageDown,
+            _ => Key::Char(event.character?),
+        };
+        Some(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Ground,
+    /// After `ESC`.
+    Escape,
+    /// In a control sequence (`ESC [`), with its first parameter so far. Further parameters,
+    /// such as the modifiers of `ESC [ 1 ; 5 C`, are skipped.
+    Csi { parameter: u16, skipping: bool },
+    /// After `ESC O`, which some terminals send cursor keys with.
+    Ss3,
+    /// In a UTF-8 sequence, with the bytes received so far.
+    Utf8 { bytes: [u8; 4], len: usize, expected: usize },
+}
+
+/// Turns the bytes a serial terminal sends (VT100 key sequences and UTF-8 text) into keys.
+pub struct TerminalDecoder {
+    state: State,
+    /// The last byte ended a line with a carriage return, so a line feed after it is dropped.
+    after_cr: bool,
+}
+
+impl TerminalDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            after_cr: false,
+        }
+    }
+
+    /// Feeds in the next byte and returns the key it completes, if any.
+    pub fn advance(&mut self, byte: u8) -> Option<Key> {
+        let after_cr = self.after_cr;
+        self.after_cr = byte == b'\r';
+        match self.state {
+            State::Ground => match byte {
+                0x1b => {
+                    self.state = State::Escape;
+                    None
+                }
+                b'\r' => Some(Key::Char('\n')),
+                b'\n' if after_cr => None,
+                // terminals send DEL for the backspace key
+                0x7f => Some(Key::Char(BACKSPACE)),
+                0x00..=0x7f => Some(Key::Char(byte as char)),
+                _ => {
+                    let expected = match byte {
+                        0xc0..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        0xf0..=0xf7 => 4,
+                        // a stray continuation byte
+                        _ => return None,
+                    };
+                    let mut bytes = [0; 4];
+                    bytes[0] = byte;
+                    self.state = State::Utf8 {
+                        bytes,
+                        len: 1,
+                        expected,
+                    };
+                    None
+                }
+            },
+            State::Escape => {
+                self.state = match byte {
+                    b'[' => State::Csi {
+                        parameter: 0,
+                        skipping: false,
+                    },
+                    b'O' => State::Ss3,
+                    _ => State::Ground,
+                };
+                None
+            }
+            State::Csi {
+                parameter,
+                skipping,
+            } => match byte {
+                b'0'..=b'9' if !skipping => {
+                    let digit = (byte - b'0') as u16;
+                    self.state = State::Csi {
+                        parameter: parameter.saturating_mul(10).saturating_add(digit),
+                        skipping,
+                    };
+                    None
+                }
+                b'0'..=b'9' | b';' => {
+                    self.state = State::Csi {
+                        parameter,
+                        skipping: true,
+                    };
+                    None
+                }
+                _ => {
+                    self.state = State::Ground;
+                    match (byte, parameter) {
+                        (b'~', 1 | 7) => Some(Key::Home),
+                        (b'~', 3) => Some(Key::Delete),
+                        (b'~', 4 | 8) => Some(Key::End),
+                        (b'~', 5) => Some(Key::PageUp),
+                        (b'~', 6) => Some(Key::PageDown),
+                        _ => cursor_key(byte),
+                    }
+                }
+            },
+            State::Ss3 => {
+                self.state = State::Ground;
+                cursor_key(byte)
+            }
+            State::Utf8 {
+                mut bytes,
+                len,
+                expected,
+            } => {
+                if byte & 0xc0 != 0x80 {
+                    // the sequence broke off; start over with this byte
+                    self.state = State::Ground;
+                    return self.advance(byte);
+                }
+                bytes[len] = byte;
+                if len + 1 < expected {
+                    self.state = State::Utf8 {
+                        bytes,
+                        len: len + 1,
+                        expected,
+                    };
+                    return None;
+                }
+                self.state = State::Ground;
+                let text = core::str::from_utf8(&bytes[..expected]).ok()?;
+                text.chars().next().map(Key::Char)
+            }
+        }
+    }
+}
+
+/// The key ending a cursor key sequence such as `ESC [ A`.
+fn cursor_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        _ => None,
+    }
+}
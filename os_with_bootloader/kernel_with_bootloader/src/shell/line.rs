@@ -0,0 +1,91 @@
+/// Longest line the shell accepts, in characters.
+pub const MAX_LINE: usize = 128;
+
+/// Number of previously entered lines kept for recall with the arrow keys.
+const HISTORY_SIZE: usize = 16;
+
+/// A line of input being edited or kept in the history.
+#[derive(Clone, Copy)]
+pub struct Line {
+    chars: [char; MAX_LINE],
+    len: usize,
+}
+
+impl Line {
+    pub const fn new() -> Self {
+        Self {
+            chars: ['\0'; MAX_LINE],
+            len: 0,
+        }
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Inserts `c` before the character at `index`. Returns false if the line is full.
+    pub fn insert(&mut self, index: usize, c: char) -> bool {
+        if self.len == MAX_LINE {
+            return false;
+        }
+        self.chars.copy_within(index..self.len, index + 1);
+        self.chars[index] = c;
+        self.len += 1;
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.chars.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+    }
+
+    /// Encodes the line as UTF-8 into `buffer`.
+    pub fn encode<'a>(&self, buffer: &'a mut [u8; MAX_LINE * 4]) -> &'a str {
+        let mut end = 0;
+        for c in self.chars() {
+            end += c.encode_utf8(&mut buffer[end..]).len();
+        }
+        // only whole characters were encoded, so this cannot fail
+        core::str::from_utf8(&buffer[..end]).unwrap_or_default()
+    }
+}
+
+/// The most recently entered lines, oldest ones dropped first.
+pub struct History {
+    lines: [Line; HISTORY_SIZE],
+    /// Number of lines ever added; the newest one is at `(added - 1) % HISTORY_SIZE`.
+    added: usize,
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self {
+            lines: [Line::new(); HISTORY_SIZE],
+            added: 0,
+        }
+    }
+
+    /// Remembers `line` unless it is blank or repeats the newest entry.
+    pub fn add(&mut self, line: &Line) {
+        if line.chars().iter().all(|c| c.is_whitespace()) {
+            return;
+        }
+        if self.get(0).is_some_and(|newest| newest.chars() == line.chars()) {
+            return;
+        }
+        self.lines[self.added % HISTORY_SIZE] = *line;
+        self.added += 1;
+    }
+
+    /// Returns the line entered `age` lines ago, 0 being the newest.
+    pub fn get(&self, age: usize) -> Option<&Line> {
+        if age >= self.added.min(HISTORY_SIZE) {
+            return None;
+        }
+        Some(&self.lines[(self.added - 1 - age) % HISTORY_SIZE])
+    }
+}
@@ -0,0 +1,41 @@
+use spin::Mutex;
+
+use crate::graphics::{Bitmap, Color, Point, Scaled};
+use crate::image::{self, ImageError};
+use crate::writer;
+
+/// The logo shown at boot unless the ramdisk holds an image.
+static LOGO: &[u8] = include_bytes!("../assets/logo.qoi");
+
+/// Largest image, in pixels, that can be decoded.
+const MAX_PIXELS: usize = 512 * 512;
+
+/// Images are shrunk to at most this fraction of the screen in either direction. Smaller ones
+/// are shown at their own size.
+const MAX_SCREEN_SHARE: usize = 2;
+
+static PIXELS: Mutex<[Option<Color>; MAX_PIXELS]> = Mutex::new([None; MAX_PIXELS]);
+
+/// Draws `data` (a BMP, TGA or QOI file) or, without it, the built-in logo in the middle of the
+/// screen. Console output written afterwards is drawn over it.
+pub fn show(data: Option<&[u8]>) -> Result<(), ImageError> {
+    let mut pixels = PIXELS.lock();
+    let image = image::decode(data.unwrap_or(LOGO), &mut *pixels)?;
+    writer::with_writer(|writer| {
+        let mut canvas = writer.canvas();
+        let (max_width, max_height) =
+            (canvas.width() / MAX_SCREEN_SHARE, canvas.height() / MAX_SCREEN_SHARE);
+        let scaled = if image.width() > max_width || image.height() > max_height {
+            Scaled::fit(&image, max_width, max_height)
+        } else {
+            Scaled::new(&image, image.width(), image.height())
+        };
+        let corner = Point::new(
+            (canvas.width() - scaled.width()) as isize / 2,
+            (canvas.height() - scaled.height()) as isize / 2,
+        );
+        canvas.blit(corner, &scaled);
+        writer.flush();
+    });
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Cooperative multitasking. Kernel tasks are futures that one executor polls until they finish.
+//! A task keeps the CPU until it awaits something that is not ready yet, so tasks must not spin
+//! or block.
+
+mod executor;
+mod input;
+
+pub use input::InputQueue;
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+use crossbeam_queue::ArrayQueue;
+use spin::Once;
+
+use executor::Executor;
+
+/// Most tasks that can exist at the same time.
+pub const MAX_TASKS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A future the executor runs to completion.
+pub struct Task {
+    id: TaskId,
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Task {
+    pub fn new(future: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self {
+            id: TaskId::new(),
+            future: Box::pin(future),
+        }
+    }
+
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Task").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+/// Tasks that have been spawned but not picked up by the executor yet.
+static SPAWNED: Once<ArrayQueue<Task>> = Once::new();
+
+/// Tasks that have been spawned and have not finished.
+static TASK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn spawned() -> &'static ArrayQueue<Task> {
+    SPAWNED.call_once(|| ArrayQueue::new(MAX_TASKS))
+}
+
+/// Queues `task` for the executor, which starts polling it once [run] gets to it. There is no
+/// room for it while [MAX_TASKS] tasks exist.
+pub fn spawn(task: Task) -> Result<TaskId, Task> {
+    let id = task.id;
+    let reserved = TASK_COUNT.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+        (count < MAX_TASKS).then_some(count + 1)
+    });
+    if reserved.is_err() {
+        return Err(task);
+    }
+    // the queue has room for every task there can be
+    spawned().push(task).inspect_err(|_| {
+        TASK_COUNT.fetch_sub(1, Ordering::AcqRel);
+    })?;
+    Ok(id)
+}
+
+/// Runs spawned tasks forever, halting the CPU whenever none of them can make progress. Tasks
+/// are woken by interrupt handlers, so interrupts must be enabled.
+pub fn run() -> ! {
+    static RUNNING: AtomicBool = AtomicBool::new(false);
+    assert!(!RUNNING.swap(true, Ordering::SeqCst), "the executor is already running");
+    Executor::new().run()
+}
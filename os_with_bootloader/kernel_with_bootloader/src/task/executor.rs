@@ -0,0 +1,108 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Waker};
+
+use crossbeam_queue::ArrayQueue;
+use x86_64::instructions::interrupts;
+
+use super::{spawned, Task, TaskId, MAX_TASKS, TASK_COUNT};
+
+/// Polls the tasks whose wakers have been called, in the order they were woken.
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Entry>,
+    /// Tasks to poll next. Shared with the wakers, which interrupt handlers call.
+    ready: Arc<ArrayQueue<TaskId>>,
+}
+
+struct Entry {
+    task: Task,
+    waker: Arc<TaskWaker>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            ready: Arc::new(ArrayQueue::new(MAX_TASKS)),
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.admit_spawned();
+            self.poll_ready();
+            self.idle();
+        }
+    }
+
+    /// Takes over newly spawned tasks and polls each of them once.
+    fn admit_spawned(&mut self) {
+        while let Some(task) = spawned().pop() {
+            let waker = Arc::new(TaskWaker {
+                id: task.id,
+                ready: self.ready.clone(),
+                queued: AtomicBool::new(false),
+            });
+            waker.schedule();
+            self.tasks.insert(task.id, Entry { task, waker });
+        }
+    }
+
+    /// Polls the tasks that were ready when this was called. Tasks woken meanwhile wait for the
+    /// next round, so that one that keeps waking itself cannot starve newly spawned ones.
+    fn poll_ready(&mut self) {
+        for _ in 0..self.ready.len() {
+            let Some(id) = self.ready.pop() else { break };
+            let Some(entry) = self.tasks.get_mut(&id) else { continue };
+            // wakeups from here on poll the task again
+            entry.waker.queued.store(false, Ordering::Release);
+            let waker = Waker::from(entry.waker.clone());
+            if entry.task.poll(&mut Context::from_waker(&waker)).is_ready() {
+                // wakers can outlive their task, but must not queue it any more
+                entry.waker.queued.store(true, Ordering::Release);
+                self.tasks.remove(&id);
+                TASK_COUNT.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    }
+
+    /// Halts until the next interrupt if no task can run.
+    fn idle(&self) {
+        interrupts::disable();
+        if self.ready.is_empty() && spawned().is_empty() {
+            // enabling interrupts and halting in one step so that no wakeup slips in between
+            interrupts::enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+struct TaskWaker {
+    id: TaskId,
+    ready: Arc<ArrayQueue<TaskId>>,
+    /// Set while the task is in the ready queue, so that it is queued once however often it is
+    /// woken.
+    queued: AtomicBool,
+}
+
+impl TaskWaker {
+    fn schedule(&self) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            // every task is in the queue at most once, and there are at most MAX_TASKS of them
+            self.ready.push(self.id).expect("ready queue overflowed");
+        }
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.schedule();
+    }
+}
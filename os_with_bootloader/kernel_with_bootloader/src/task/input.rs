@@ -0,0 +1,46 @@
+use core::task::{Context, Poll};
+
+use futures_util::task::AtomicWaker;
+
+use crate::ring_buffer::RingBuffer;
+
+/// Values an interrupt handler produces for a task to consume, which sleeps while there are
+/// none. Like [RingBuffer], this has one producer and one consumer; a second task waiting on the
+/// same queue would take the first one's place and leave it asleep.
+pub struct InputQueue<T: Copy, const N: usize> {
+    buffer: RingBuffer<T, N>,
+    waker: AtomicWaker,
+}
+
+impl<T: Copy, const N: usize> InputQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: RingBuffer::new(),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Appends `value` and wakes the consumer, or hands the value back if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let result = self.buffer.push(value);
+        self.waker.wake();
+        result
+    }
+
+    /// Removes the oldest value, or arranges for the task polling with `context` to be woken
+    /// once there is one.
+    pub fn poll_pop(&self, context: &mut Context) -> Poll<T> {
+        if let Some(value) = self.buffer.pop() {
+            return Poll::Ready(value);
+        }
+        self.waker.register(context.waker());
+        // a value pushed before the waker was registered would not have woken anyone
+        match self.buffer.pop() {
+            Some(value) => {
+                self.waker.take();
+                Poll::Ready(value)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
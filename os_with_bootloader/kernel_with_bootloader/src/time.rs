@@ -0,0 +1,84 @@
+mod pit;
+mod sleep;
+
+pub use sleep::Sleep;
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::time::Duration;
+
+use x86_64::instructions::{hlt, interrupts};
+
+use crate::interrupts::set_irq_handler;
+use crate::registry::Registry;
+
+/// Rate at which the timer interrupt advances the tick counter.
+pub const TIMER_FREQUENCY_HZ: u32 = 100;
+
+/// IRQ line of the tick interrupt. The PIT is wired to it, and the local APIC timer takes over
+/// its vector once the APIC is enabled.
+pub const TIMER_IRQ: u8 = 0;
+
+/// Called from the timer interrupt after every tick.
+pub type TickHook = fn();
+
+/// Maximum number of hooks that can be registered at the same time.
+const MAX_TICK_HOOKS: usize = 4;
+
+static TICK_HOOKS: Registry<TickHook, MAX_TICK_HOOKS> = Registry::new();
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// Frequency the timer was actually programmed to, used to turn ticks into time.
+static FREQUENCY_HZ: AtomicU32 = AtomicU32::new(TIMER_FREQUENCY_HZ);
+
+/// Starts the PIT at [TIMER_FREQUENCY_HZ] and hooks it up to the tick counter.
+pub fn init() {
+    let frequency = pit::set_frequency(TIMER_FREQUENCY_HZ);
+    FREQUENCY_HZ.store(frequency, Ordering::Relaxed);
+    set_irq_handler(TIMER_IRQ, on_tick);
+}
+
+/// Runs `hook` on every timer tick from now on. Fails if there are [MAX_TICK_HOOKS] already.
+pub fn register_tick_hook(hook: TickHook) -> Result<(), TickHook> {
+    TICK_HOOKS.register(hook)
+}
+
+fn on_tick() {
+    let now = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    sleep::wake_expired(now);
+    for hook in TICK_HOOKS.items() {
+        hook();
+    }
+}
+
+/// Number of timer interrupts since [init].
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Timer interrupts per second.
+pub fn frequency() -> u32 {
+    FREQUENCY_HZ.load(Ordering::Relaxed)
+}
+
+/// Time since the timer was started, at tick resolution.
+pub fn uptime() -> Duration {
+    let nanos = ticks() as u128 * 1_000_000_000 / frequency() as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Halts until `ticks` more timer interrupts have arrived. Interrupts must be enabled, otherwise
+/// this never returns.
+pub fn sleep_ticks(ticks: u64) {
+    debug_assert!(interrupts::are_enabled());
+    let deadline = self::ticks() + ticks;
+    while self::ticks() < deadline {
+        hlt();
+    }
+}
+
+/// Completes once `duration` has passed, rounded up to whole ticks, letting other tasks run in
+/// the meantime.
+pub fn sleep(duration: Duration) -> Sleep {
+    let ticks = (duration.as_nanos() * frequency() as u128).div_ceil(1_000_000_000);
+    Sleep::until(self::ticks() + ticks as u64)
+}
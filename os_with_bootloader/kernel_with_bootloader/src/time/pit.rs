@@ -0,0 +1,25 @@
+use x86_64::instructions::port::Port;
+
+/// Input clock of the Programmable Interval Timer.
+pub const BASE_FREQUENCY_HZ: u32 = 1_193_182;
+
+const CHANNEL_0: u16 = 0x40;
+const COMMAND: u16 = 0x43;
+
+/// Channel 0, low byte then high byte, mode 3 (square wave), binary counting.
+const COMMAND_CHANNEL_0_SQUARE_WAVE: u8 = 0b0011_0110;
+
+/// Programs channel 0 to fire IRQ0 at roughly `hz` times a second and returns the frequency
+/// actually achieved, which differs slightly because the divisor is an integer.
+pub fn set_frequency(hz: u32) -> u32 {
+    // A divisor of 0 stands for 65536, the slowest rate the PIT can do.
+    let divisor = (BASE_FREQUENCY_HZ + hz / 2) / hz.max(1);
+    let divisor = divisor.clamp(1, 0x1_0000);
+    unsafe {
+        Port::new(COMMAND).write(COMMAND_CHANNEL_0_SQUARE_WAVE);
+        let mut channel_0 = Port::<u8>::new(CHANNEL_0);
+        channel_0.write(divisor as u8);
+        channel_0.write((divisor >> 8) as u8);
+    }
+    BASE_FREQUENCY_HZ / divisor
+}
@@ -0,0 +1,73 @@
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// A task waiting for the tick counter to reach its deadline.
+struct Sleeper {
+    id: u64,
+    deadline: u64,
+    waker: Waker,
+}
+
+static SLEEPERS: Mutex<Vec<Sleeper>> = Mutex::new(Vec::new());
+
+/// Future returned by [super::sleep].
+pub struct Sleep {
+    id: u64,
+    deadline: u64,
+}
+
+impl Sleep {
+    pub(super) fn until(deadline: u64) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            deadline,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+        // with interrupts disabled no tick can pass between the check and registering
+        interrupts::without_interrupts(|| {
+            if super::ticks() >= self.deadline {
+                return Poll::Ready(());
+            }
+            let mut sleepers = SLEEPERS.lock();
+            match sleepers.iter_mut().find(|sleeper| sleeper.id == self.id) {
+                Some(sleeper) => sleeper.waker.clone_from(context.waker()),
+                None => sleepers.push(Sleeper {
+                    id: self.id,
+                    deadline: self.deadline,
+                    waker: context.waker().clone(),
+                }),
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| SLEEPERS.lock().retain(|sleeper| sleeper.id != self.id));
+    }
+}
+
+/// Wakes the tasks whose deadline is `now` or earlier. Called from the timer interrupt.
+pub(super) fn wake_expired(now: u64) {
+    SLEEPERS.lock().retain(|sleeper| {
+        let expired = sleeper.deadline <= now;
+        if expired {
+            sleeper.waker.wake_by_ref();
+        }
+        !expired
+    });
+}
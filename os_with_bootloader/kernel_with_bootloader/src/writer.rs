@@ -1,14 +1,31 @@
-mod constants;
+mod ansi;
+pub mod constants;
+pub mod font;
+mod scrollback;
+
+pub use crate::graphics::Color;
+pub use font::Font;
 
 use core::{
+     array,
      fmt::{self, Write},
+     ops::Range,
      ptr,
+     sync::atomic::{AtomicUsize, Ordering},
+     time::Duration,
 }; 
     
-use bootloader_api::info::{FrameBufferInfo, PixelFormat}; 
+use alloc::vec::Vec;
+use bootloader_api::info::{FrameBuffer, FrameBufferInfo}; 
 use constants::font_constants; 
-use constants::font_constants::{BACKUP_CHAR, CHAR_RASTER_HEIGHT, FONT_WEIGHT}; 
-use noto_sans_mono_bitmap::{get_raster, RasterizedChar}; 
+use ansi::{Action, Csi};
+use font::Glyph;
+use scrollback::{Cell, Scrollback};
+use crate::graphics::{Bitmap, Canvas, Point};
+use crate::screen::Screen;
+use crate::time;
+use spin::{Mutex, Once};
+use x86_64::instructions::interrupts;
 
 /// Additional vertical space between lines 
 const LINE_SPACING: usize= 2; 
@@ -19,49 +36,169 @@ const LETTER_SPACING: usize= 0;
 /// Padding from the border. Prevent that font is too close to border. 
 const BORDER_PADDING: usize= 1;
 
-/// Returns the raster of the given char or the raster of [font_constants::BACKUP_CHAR]. 
-fn get_char_raster(c: char) -> RasterizedChar{
-     fn get(c: char) -> Option<RasterizedChar> {
-         get_raster(c, FONT_WEIGHT, CHAR_RASTER_HEIGHT) 
-        } 
-        get(c).unwrap_or_else(|| get(BACKUP_CHAR).expect("Should get raster of backup char.")) 
-}
+/// Height of the underline that marks the cursor position, in pixels.
+const CARET_HEIGHT: usize = 2;
+
+/// Brightness of dim text (SGR 2) relative to normal text, out of 255.
+const DIM_INTENSITY: u8 = 160;
+
+/// Number of virtual consoles, switched between with Alt+F1 and so on.
+pub const CONSOLES: usize = 6;
+
+/// The console kernel log records are written to, shown at boot.
+pub const LOG_CONSOLE: usize = 0;
+
+/// The console [print!] and [with_writer] write to, used by the shell.
+pub const SHELL_CONSOLE: usize = 1;
+
 /// Allows logging text to a pixel-based framebuffer. 
 pub struct FrameBufferWriter{
-     framebuffer: &'static mut [u8], 
+     screen: Screen, 
      info: FrameBufferInfo, 
+     font: Font,
+     /// Where the caret is drawn, so that it can be removed before anything is written.
+     caret_drawn: Option<(usize, usize)>,
+     /// The caret is in the hidden half of its blink, see [blink_caret].
+     caret_blinked_off: bool,
+     /// The virtual console being written to. Only the [Self::active] one is drawn.
+     console: VirtualConsole,
+     /// Index of [Self::console].
+     current: usize,
+     /// The other virtual consoles, by index; the slot of the current one is empty.
+     parked: [Option<VirtualConsole>; CONSOLES],
+     /// Index of the console shown on the screen.
+     active: usize,
+}
+
+/// The text grid, cursor and attributes each virtual console has its own copy of.
+struct VirtualConsole {
      x_pos: usize, 
      y_pos: usize, 
+     foreground: Color,
+     background: Color,
+     caret_visible: bool,
+     scrollback: &'static mut Scrollback,
+     /// How many lines back in the scrollback the screen shows; 0 is the live output.
+     view_offset: usize,
+     escape_parser: ansi::Parser,
+     bold: bool,
+     dim: bool,
+     /// Cursor and text attributes remembered by `ESC 7` or `ESC [ s`.
+     saved_cursor: Option<SavedCursor>,
+}
+
+impl VirtualConsole {
+    fn new(scrollback: &'static mut Scrollback) -> Self {
+        Self {
+            x_pos: BORDER_PADDING,
+            y_pos: BORDER_PADDING,
+            foreground: Color::DEFAULT_FOREGROUND,
+            background: Color::DEFAULT_BACKGROUND,
+            caret_visible: false,
+            scrollback,
+            view_offset: 0,
+            escape_parser: ansi::Parser::new(),
+            bold: false,
+            dim: false,
+            saved_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SavedCursor {
+    x_pos: usize,
+    y_pos: usize,
+    foreground: Color,
+    background: Color,
+    bold: bool,
+    dim: bool,
 }
 
 impl FrameBufferWriter{ 
-    /// Creates a new logger that uses the given framebuffer. 
+    /// Creates a new logger that uses the given framebuffer, keeping the text that scrolls off
+    /// the screen of each virtual console in its entry of `scrollbacks`. The log console is shown
+    /// and the shell console written to.
     pub fn new(framebuffer: &'static mut [u8], info: 
-    FrameBufferInfo) -> Self {
+    FrameBufferInfo, scrollbacks: [&'static mut Scrollback; CONSOLES]) -> Self {
+         let mut parked = scrollbacks.map(|scrollback| Some(VirtualConsole::new(scrollback)));
+         let console = parked[SHELL_CONSOLE].take().expect("every console starts parked");
          let mut logger = Self {
-             framebuffer, 
+             screen: Screen::new(framebuffer, info), 
              info, 
-             x_pos: 0, 
-             y_pos: 0, 
+             font: Font::DEFAULT,
+             caret_drawn: None,
+             caret_blinked_off: false,
+             console,
+             current: SHELL_CONSOLE,
+             parked,
+             active: LOG_CONSOLE,
         }; 
-        logger.clear(); 
+        for index in 0..CONSOLES {
+            logger.with_console(index, |logger| logger.clear());
+        }
         logger 
     }
 
+    /// Runs `f` with console `index` as the one written to.
+    pub fn with_console<R>(&mut self, index: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = self.current;
+        self.select(index);
+        let result = f(self);
+        self.select(previous);
+        result
+    }
+
+    /// Makes console `index` the one written to, parking the current one.
+    fn select(&mut self, index: usize) {
+        if index == self.current || index >= CONSOLES {
+            return;
+        }
+        let console = self.parked[index].take().expect("only the current console is not parked");
+        self.parked[self.current] = Some(core::mem::replace(&mut self.console, console));
+        self.current = index;
+    }
+
+    /// Whether the console being written to is on the screen.
+    fn drawing(&self) -> bool {
+        self.current == self.active
+    }
+
+    /// Index of the console shown on the screen.
+    pub fn active_console(&self) -> usize {
+        self.active
+    }
+
+    /// Shows console `index`, redrawing the screen from its text grid.
+    pub fn switch_to(&mut self, index: usize) {
+        if index == self.active || index >= CONSOLES {
+            return;
+        }
+        self.with_console(self.active, |writer| writer.hide_caret());
+        self.active = index;
+        ACTIVE_CONSOLE.store(index, Ordering::Relaxed);
+        self.with_console(index, |writer| writer.repaint());
+    }
+
     fn newline(&mut self) {
-         self.y_pos+= font_constants::CHAR_RASTER_HEIGHT.val() + LINE_SPACING; 
+         self.console.y_pos+= self.line_height(); 
          self.carriage_return() 
         } 
         
         fn carriage_return(&mut self) {
-             self.x_pos= BORDER_PADDING; 
+             self.console.x_pos= BORDER_PADDING; 
         } 
         
     /// Erases all text on the screen. Resets self.x_posand self.y_pos. 
     pub fn clear(&mut self) {
-         self.x_pos= BORDER_PADDING; 
-         self.y_pos= BORDER_PADDING; 
-         self.framebuffer.fill(0); 
+         self.hide_caret();
+         self.console.x_pos= BORDER_PADDING; 
+         self.console.y_pos= BORDER_PADDING; 
+         self.fill_rows(0..self.height()); 
+         let rows = self.rows();
+         self.console.scrollback.clear_screen(rows);
+         self.console.view_offset = 0;
+         self.show_caret();
     }
 
     fn width(&self) -> usize{
@@ -73,58 +210,486 @@ impl FrameBufferWriter{
     }
 
 /// Writes a single char to the framebuffer. Takes care of special control characters, such as 
-/// newlines and carriage returns. 
+/// newlines and carriage returns, and of ANSI escape sequences. 
 fn write_char(&mut self, c: char) {
+     self.hide_caret();
+     let c = match self.console.escape_parser.advance(c) {
+         Some(Action::Print(c)) => c,
+         Some(action) => return self.perform(action),
+         None => return,
+     };
      match c { 
-    '\n' => self.newline(), 
+    '\n' => {
+         self.newline();
+         self.flush();
+    }
     '\r' => self.carriage_return(), 
+    font_constants::BACKSPACE => self.backspace(), 
     c => {
-         let new_xpos= self.x_pos+ font_constants::CHAR_RASTER_WIDTH; 
+         let new_xpos= self.console.x_pos+ self.font.width(); 
          if new_xpos>= self.width() {
              self.newline(); 
         } 
-        let new_ypos= self.y_pos+ font_constants::CHAR_RASTER_HEIGHT.val() + BORDER_PADDING; 
+        let new_ypos= self.console.y_pos+ self.font.height() + BORDER_PADDING; 
         if new_ypos>= self.height() {
-             self.clear(); 
+             self.scroll_to_fit(new_ypos); 
         } 
-        self.write_rendered_char(get_char_raster(c)); 
+        let (row, column) = self.cursor_cell();
+        let cell = Cell {
+            c,
+            foreground: self.text_color(),
+            background: self.console.background,
+            bold: self.console.bold,
+        };
+        self.console.scrollback.set(row, column, cell);
+        self.write_glyph(self.font.glyph(c, self.console.bold)); 
         } 
     } 
 }
 
-/// Prints a rendered char into the framebuffer. 
+/// Prints a glyph into the framebuffer if the console is shown. 
 /// Updates self.x_pos. 
-fn write_rendered_char(&mut self, rendered_char: RasterizedChar) {
-     for (y, row) in rendered_char.raster().iter().enumerate() {
-         for (x, byte) in row.iter().enumerate() {
-             self.write_pixel(self.x_pos+ x, self.y_pos+ y, *byte); 
-            } 
-        } self.x_pos+= rendered_char.width() + LETTER_SPACING; 
-}
-
-fn write_pixel(&mut self, x: usize, y: usize, intensity: u8) {
-     let pixel_offset= y * self.info.stride+ x; 
-     let color = match self.info.pixel_format{
-         PixelFormat::Rgb=> [intensity, intensity, intensity / 2, 0], 
-         PixelFormat::Bgr=> [intensity / 2, intensity, intensity, 0], 
-         PixelFormat::U8 => [if intensity > 200 { 0xf } else { 0 }, 0, 0, 0], 
-         other => {
-             // set a supported (but invalid) pixel format before panicking to avoid a double 
-             // panic; it might not be readable though 
-             self.info.pixel_format= PixelFormat::Rgb; 
-             panic!("pixel format {:?} not supported in logger", other) 
-            } 
-        }; 
-        let bytes_per_pixel= self.info.bytes_per_pixel; 
-        let byte_offset= pixel_offset* bytes_per_pixel; 
-        self.framebuffer[byte_offset..(byte_offset+ bytes_per_pixel)]
-         .copy_from_slice(&color[..bytes_per_pixel]); 
-        let _ = unsafe { ptr::read_volatile(&self.framebuffer[byte_offset]) }; 
-    } 
+fn write_glyph(&mut self, glyph: Glyph) {
+     if self.drawing() {
+         let corner = cell_corner(self.console.x_pos, self.console.y_pos);
+         let bitmap = TextGlyph {
+             glyph,
+             foreground: self.text_color(),
+             background: self.console.background,
+         };
+         self.canvas().blit(corner, &bitmap);
+        }
+        self.console.x_pos+= self.cell_width(); 
+}
+
+    /// Draws on the screen, over whatever console is shown. Text written later, scrolling and
+    /// switching consoles draw over the drawing.
+    pub fn canvas(&mut self) -> Canvas<'_> {
+        Canvas::new(&mut self.screen)
+    }
 
     pub fn change_cursor_position(&mut self, x_pos: usize, y_pos: usize){
-        self.x_pos = x_pos;
-        self.y_pos = y_pos;
+        self.hide_caret();
+        self.console.x_pos = x_pos;
+        self.console.y_pos = y_pos;
+        self.show_caret();
+    }
+
+    /// Moves the cursor back one character without erasing it, like a terminal does. At the
+    /// start of a row it continues at the last column of the row above, matching how
+    /// [Self::write_char] wraps long lines.
+    fn backspace(&mut self) {
+        let (line_height, cell_width) = (self.line_height(), self.cell_width());
+        if self.console.x_pos >= BORDER_PADDING + cell_width {
+            self.console.x_pos -= cell_width;
+            return;
+        }
+        if self.console.y_pos < BORDER_PADDING + line_height {
+            return;
+        }
+        self.console.y_pos -= line_height;
+        self.console.x_pos = BORDER_PADDING + (self.columns() - 1) * cell_width;
+    }
+
+    /// Distance between the tops of two text lines.
+    fn line_height(&self) -> usize {
+        self.font.height() + LINE_SPACING
+    }
+
+    /// Distance between the left edges of two characters.
+    fn cell_width(&self) -> usize {
+        self.font.width() + LETTER_SPACING
+    }
+
+    /// Number of text rows that fit on the screen.
+    fn rows(&self) -> usize {
+        let room = self.height().saturating_sub(2 * BORDER_PADDING + self.font.height() + 1);
+        room / self.line_height() + 1
+    }
+
+    /// Row and column of the text cell the cursor is in.
+    fn cursor_cell(&self) -> (usize, usize) {
+        let row = self.console.y_pos.saturating_sub(BORDER_PADDING) / self.line_height();
+        let column = self.console.x_pos.saturating_sub(BORDER_PADDING) / self.cell_width();
+        (row, column)
+    }
+
+    pub fn font(&self) -> Font {
+        self.font
+    }
+
+    /// Switches every console to `font` and redraws the screen with it. The line metrics follow
+    /// the font, so the number of rows and columns may change; cursors stay in their text cell,
+    /// scrolling their console if that row no longer fits.
+    pub fn set_font(&mut self, font: Font) {
+        self.with_console(self.active, |writer| writer.hide_caret());
+        let old_rows = self.rows();
+        let cells: [_; CONSOLES] =
+            array::from_fn(|index| self.with_console(index, |writer| writer.cursor_cell()));
+        self.font = font;
+        for (index, (row, column)) in cells.into_iter().enumerate() {
+            self.with_console(index, |writer| {
+                let rows = writer.rows();
+                let excess = (row + 1).saturating_sub(rows);
+                let history = &mut writer.console.scrollback;
+                history.scroll(excess, old_rows);
+                // once the ring has wrapped, rows that come into view below the old screen hold
+                // the oldest history lines, which must not be shown there or paged back to twice
+                history.set_limit(history.limit(), rows);
+                for row in old_rows..rows {
+                    history.erase(row, 0..scrollback::MAX_COLUMNS);
+                }
+                writer.move_to_cell(row - excess, column);
+                writer.console.view_offset = 0;
+            });
+        }
+        self.with_console(self.active, |writer| writer.repaint());
+    }
+
+    /// Moves the screen contents up by as many lines as it takes for a character whose bottom
+    /// edge would be at `bottom` to fit, and clears the lines that come into view.
+    fn scroll_to_fit(&mut self, bottom: usize) {
+        let line_height = self.line_height();
+        let lines = (bottom - self.height()) / line_height + 1;
+        if self.drawing() {
+            self.canvas().scroll_up(lines * line_height);
+        }
+        self.fill_rows(self.height().saturating_sub(lines * line_height)..self.height());
+        self.console.y_pos = self.console.y_pos.saturating_sub(lines * line_height);
+        let rows = self.rows();
+        self.console.scrollback.scroll(lines.min(rows), rows);
+    }
+
+    /// Pages back through the lines that scrolled off the top of the screen. Writing anything
+    /// returns to the live output.
+    pub fn page_up(&mut self) {
+        let page = self.rows().saturating_sub(1).max(1);
+        self.set_view_offset(self.console.view_offset + page);
+    }
+
+    pub fn page_down(&mut self) {
+        let page = self.rows().saturating_sub(1).max(1);
+        self.set_view_offset(self.console.view_offset.saturating_sub(page));
+    }
+
+    /// Sets how many lines that scrolled off the screen are kept for paging back to. The
+    /// scrollback has a fixed capacity, so the limit that took effect is returned.
+    pub fn set_scrollback_limit(&mut self, lines: usize) -> usize {
+        let rows = self.rows();
+        let limit = self.console.scrollback.set_limit(lines, rows);
+        self.set_view_offset(self.console.view_offset);
+        limit
+    }
+
+    pub fn scrollback_limit(&self) -> usize {
+        self.console.scrollback.limit()
+    }
+
+    fn set_view_offset(&mut self, offset: usize) {
+        let offset = offset.min(self.console.scrollback.history_len());
+        if offset != self.console.view_offset {
+            self.console.view_offset = offset;
+            self.repaint();
+        }
+    }
+
+    /// Redraws the screen from the scrollback, `view_offset` lines back.
+    fn repaint(&mut self) {
+        if !self.drawing() {
+            return;
+        }
+        self.hide_caret();
+        self.fill_rows(0..self.height());
+        let saved = self.save_cursor();
+        self.console.dim = false;
+        for row in 0..self.rows() {
+            for column in 0..scrollback::MAX_COLUMNS {
+                let cell = self.console.scrollback.row(row, self.console.view_offset)[column];
+                self.console.x_pos = BORDER_PADDING + column * self.cell_width();
+                if self.console.x_pos + self.font.width() >= self.width() {
+                    break;
+                }
+                if cell == Cell::EMPTY {
+                    continue;
+                }
+                self.console.y_pos = BORDER_PADDING + row * self.line_height();
+                self.console.foreground = cell.foreground;
+                self.console.background = cell.background;
+                self.console.bold = cell.bold;
+                self.write_glyph(self.font.glyph(cell.c, cell.bold));
+            }
+        }
+        self.restore_cursor(saved);
+        self.show_caret();
+    }
+
+    /// Shows or hides an underline at the position the next character will be written to.
+    pub fn set_caret_visible(&mut self, visible: bool) {
+        self.hide_caret();
+        self.console.caret_visible = visible;
+        self.show_caret();
+    }
+
+    /// Shows the caret of the console on the screen if it was hidden by the last call, and
+    /// hides it otherwise.
+    pub fn blink_caret(&mut self) {
+        self.with_console(self.active, |writer| {
+            writer.hide_caret();
+            writer.caret_blinked_off = !writer.caret_blinked_off;
+            writer.show_caret();
+        });
+    }
+
+    fn show_caret(&mut self) {
+        let live = self.console.view_offset == 0 && !self.caret_blinked_off;
+        if self.console.caret_visible && self.caret_drawn.is_none() && live && self.drawing() {
+            self.invert_caret(self.console.x_pos, self.console.y_pos);
+            self.caret_drawn = Some((self.console.x_pos, self.console.y_pos));
+        }
+    }
+
+    fn hide_caret(&mut self) {
+        if !self.drawing() {
+            return;
+        }
+        if let Some((x, y)) = self.caret_drawn.take() {
+            self.invert_caret(x, y);
+        }
+    }
+
+    /// Inverts the caret area of the cell at `x`, `y`. Inverting twice restores what was there,
+    /// including the descenders of a character the cursor was moved back onto.
+    fn invert_caret(&mut self, x: usize, y: usize) {
+        let bottom = y + self.font.height();
+        let right = x + self.font.width();
+        if right > self.width() || bottom > self.height() {
+            return;
+        }
+        let corner = cell_corner(x, bottom - CARET_HEIGHT);
+        self.canvas().invert_rect(corner, right - x, CARET_HEIGHT);
+    }
+
+    /// Carries out an escape sequence decoded by the ANSI parser.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Print(c) => self.write_char(c),
+            Action::SaveCursor => self.console.saved_cursor = Some(self.save_cursor()),
+            Action::RestoreCursor => {
+                if let Some(saved) = self.console.saved_cursor {
+                    self.restore_cursor(saved);
+                }
+            }
+            Action::Csi(csi) => self.control_sequence(&csi),
+        }
+    }
+
+    fn control_sequence(&mut self, csi: &Csi) {
+        if csi.private {
+            // `ESC [ ? 25 h` and `ESC [ ? 25 l` show and hide the cursor
+            if csi.params() == [25] && matches!(csi.function, 'h' | 'l') {
+                self.console.caret_visible = csi.function == 'h';
+            }
+            return;
+        }
+        let (row, column) = self.cursor_cell();
+        let count = csi.param_or(0, 1) as usize;
+        match csi.function {
+            'A' => self.move_to_cell(row.saturating_sub(count), column),
+            'B' => self.move_to_cell(row + count, column),
+            'C' => self.move_to_cell(row, column + count),
+            'D' => self.move_to_cell(row, column.saturating_sub(count)),
+            'G' => self.move_to_cell(row, count - 1),
+            'H' | 'f' => {
+                let column = csi.param_or(1, 1) as usize - 1;
+                self.move_to_cell(count - 1, column);
+            }
+            'J' => self.erase_in_display(csi.params().first().copied().unwrap_or(0)),
+            'K' => self.erase_in_line(csi.params().first().copied().unwrap_or(0)),
+            'm' => self.select_graphic_rendition(csi.params()),
+            's' => self.console.saved_cursor = Some(self.save_cursor()),
+            'u' => {
+                if let Some(saved) = self.console.saved_cursor {
+                    self.restore_cursor(saved);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Number of characters that fit on a row before it wraps.
+    fn columns(&self) -> usize {
+        let room = self.width().saturating_sub(BORDER_PADDING + self.font.width() + 1);
+        room / self.cell_width() + 1
+    }
+
+    /// Moves the cursor to a text cell, staying on the screen.
+    fn move_to_cell(&mut self, row: usize, column: usize) {
+        let row = row.min(self.rows() - 1);
+        let column = column.min(self.columns() - 1);
+        self.console.x_pos = BORDER_PADDING + column * self.cell_width();
+        self.console.y_pos = BORDER_PADDING + row * self.line_height();
+    }
+
+    /// Blanks `columns` of screen row `row`.
+    fn erase_cells(&mut self, row: usize, columns: Range<usize>) {
+        let top = BORDER_PADDING + row * self.line_height();
+        let bottom = (top + self.line_height()).min(self.height());
+        let left = BORDER_PADDING + columns.start * self.cell_width();
+        let right = (BORDER_PADDING + columns.end * self.cell_width()).min(self.width());
+        if self.drawing() && left < right {
+            let background = self.console.background;
+            let corner = cell_corner(left, top);
+            self.canvas().fill_rect(corner, right - left, bottom - top, background);
+        }
+        self.console.scrollback.erase(row, columns);
+    }
+
+    /// `ESC [ n K`: erases from the cursor to the end of the line (0), from the start of the
+    /// line to the cursor (1), or the whole line (2).
+    fn erase_in_line(&mut self, mode: u16) {
+        let (row, column) = self.cursor_cell();
+        let columns = self.columns();
+        match mode {
+            0 => self.erase_cells(row, column..columns),
+            1 => self.erase_cells(row, 0..column + 1),
+            2 => self.erase_cells(row, 0..columns),
+            _ => {}
+        }
+    }
+
+    /// `ESC [ n J`: erases from the cursor to the end of the screen (0), from the start of the
+    /// screen to the cursor (1), or the whole screen (2 and 3). The cursor does not move.
+    fn erase_in_display(&mut self, mode: u16) {
+        let (row, _) = self.cursor_cell();
+        let (rows, columns) = (self.rows(), self.columns());
+        let full_rows = match mode {
+            0 => {
+                self.erase_in_line(0);
+                row + 1..rows
+            }
+            1 => {
+                self.erase_in_line(1);
+                0..row
+            }
+            2 | 3 => 0..rows,
+            _ => return,
+        };
+        for row in full_rows {
+            self.erase_cells(row, 0..columns);
+        }
+    }
+
+    /// `ESC [ ... m`: sets text attributes and colors.
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        // `ESC [ m` means the same as `ESC [ 0 m`
+        if params.is_empty() {
+            self.reset_attributes();
+        }
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => self.reset_attributes(),
+                1 => self.console.bold = true,
+                2 => self.console.dim = true,
+                22 => (self.console.bold, self.console.dim) = (false, false),
+                30..=37 => self.console.foreground = Color::from_ansi_256(param as u8 - 30),
+                90..=97 => self.console.foreground = Color::from_ansi_256(param as u8 - 90 + 8),
+                38 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        self.console.foreground = color;
+                    }
+                }
+                39 => self.console.foreground = Color::DEFAULT_FOREGROUND,
+                40..=47 => self.console.background = Color::from_ansi_256(param as u8 - 40),
+                100..=107 => self.console.background = Color::from_ansi_256(param as u8 - 100 + 8),
+                48 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        self.console.background = color;
+                    }
+                }
+                49 => self.console.background = Color::DEFAULT_BACKGROUND,
+                _ => {}
+            }
+        }
+    }
+
+    fn reset_attributes(&mut self) {
+        self.console.foreground = Color::DEFAULT_FOREGROUND;
+        self.console.background = Color::DEFAULT_BACKGROUND;
+        self.console.bold = false;
+        self.console.dim = false;
+    }
+
+    fn save_cursor(&self) -> SavedCursor {
+        SavedCursor {
+            x_pos: self.console.x_pos,
+            y_pos: self.console.y_pos,
+            foreground: self.console.foreground,
+            background: self.console.background,
+            bold: self.console.bold,
+            dim: self.console.dim,
+        }
+    }
+
+    fn restore_cursor(&mut self, saved: SavedCursor) {
+        self.console.x_pos = saved.x_pos;
+        self.console.y_pos = saved.y_pos;
+        self.console.foreground = saved.foreground;
+        self.console.background = saved.background;
+        self.console.bold = saved.bold;
+        self.console.dim = saved.dim;
+    }
+
+    /// The color glyphs are drawn in, taking dim text into account.
+    fn text_color(&self) -> Color {
+        if self.console.dim {
+            self.console.foreground.scaled(DIM_INTENSITY)
+        } else {
+            self.console.foreground
+        }
+    }
+
+    /// Color used for text written from now on.
+    pub fn foreground(&self) -> Color {
+        self.console.foreground
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.console.foreground = color;
+    }
+
+    /// Color behind text written from now on, and that erased areas are filled with.
+    pub fn background(&self) -> Color {
+        self.console.background
+    }
+
+    pub fn set_background(&mut self, color: Color) {
+        self.console.background = color;
+    }
+
+    /// Paints pixel rows `rows` in the background color, if the console is shown.
+    fn fill_rows(&mut self, rows: Range<usize>) {
+        if !self.drawing() {
+            return;
+        }
+        let (width, background) = (self.width(), self.console.background);
+        let height = rows.end.saturating_sub(rows.start);
+        self.canvas().fill_rect(cell_corner(0, rows.start), width, height, background);
+    }
+
+    /// Draws into `buffer` and copies changes to the framebuffer in bulk from now on. Hands the
+    /// buffer back if it is too small to hold the framebuffer.
+    pub fn enable_back_buffer(
+        &mut self,
+        buffer: &'static mut [u8],
+    ) -> Result<(), &'static mut [u8]> {
+        self.screen.enable_back_buffer(buffer)
+    }
+
+    /// Copies everything drawn since the last flush to the framebuffer. Happens by itself after
+    /// every newline and timer tick.
+    pub fn flush(&mut self) {
+        self.screen.flush();
     }
 
 }
@@ -134,32 +699,194 @@ unsafe impl Sync for FrameBufferWriter{}
 
 impl Write for FrameBufferWriter{
      fn write_str(&mut self, s: &str) -> fmt::Result {
+         // new output is always shown, so leave the scrollback
+         self.set_view_offset(0);
          for c in s.chars() {
              self.write_char(c); 
             } 
+            // the caret is shown right away after output, wherever it was in its blink
+            self.caret_blinked_off = false;
+            self.show_caret();
+            crate::console::mirror(s);
             Ok(()) 
         } 
 }
 
-// pub static WRITER: Writer = Writer {
-//     column_position: 0,
-//     color_code: ColorCode::new(Color::Yellow, Color::Black),
-//     buffer: unsafe { &mut * (0xb8000 as *mut Buffer) },
-// };
-
-// #[macro_export]
-// macro_rules! print {
-//     ($($arg:tt)*) => ($crate::io::_print(format_args!($($arg)*)));
-// }
-
-// #[macro_export]
-// macro_rules! println {
-//     () => ($crate::print!("\n"));
-//     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
-// }
-
-// #[doc(hidden)]
-// pub fn _print(args: fmt::Arguments) {
-//     use core::fmt::Write;
-//     frame_buffer_writer.write_fmt(args).unwrap();
-// }
\ No newline at end of file
+/// The kernel-wide console. Set up once by [init] from `boot_info.framebuffer`.
+pub static WRITER: Once<Mutex<FrameBufferWriter>> = Once::new();
+
+/// Initialises [WRITER] with the framebuffer handed over by the bootloader. Calling it again
+/// has no effect.
+pub fn init(framebuffer: &'static mut FrameBuffer) {
+    let info = framebuffer.info();
+    WRITER.call_once(|| {
+        static mut SCROLLBACKS: [Scrollback; CONSOLES] = [const { Scrollback::new() }; CONSOLES];
+        // call_once runs this closure at most once, so no other reference to SCROLLBACKS exists
+        let scrollbacks = unsafe { &mut *ptr::addr_of_mut!(SCROLLBACKS) };
+        let writer = FrameBufferWriter::new(framebuffer.buffer_mut(), info, scrollbacks.each_mut());
+        let _ = crate::time::register_tick_hook(on_tick);
+        Mutex::new(writer)
+    });
+}
+
+/// Index of the console shown on the screen, kept outside the lock for [active_console].
+static ACTIVE_CONSOLE: AtomicUsize = AtomicUsize::new(LOG_CONSOLE);
+
+/// Console to switch to on the next timer tick; [CONSOLES] if none.
+static REQUESTED_CONSOLE: AtomicUsize = AtomicUsize::new(CONSOLES);
+
+/// Index of the console shown on the screen.
+pub fn active_console() -> usize {
+    ACTIVE_CONSOLE.load(Ordering::Relaxed)
+}
+
+/// Shows console `index` from the next timer tick on. Safe to call from interrupt handlers,
+/// which could otherwise deadlock on the console lock.
+pub fn request_switch(index: usize) {
+    REQUESTED_CONSOLE.store(index, Ordering::Relaxed);
+}
+
+/// Runs `f` with exclusive access to the console, writing to the shell console. Interrupts are
+/// disabled while the lock is held so that an interrupt handler printing on the same core cannot
+/// deadlock against us. Returns `None` if the console has not been initialised yet.
+pub fn with_writer<R>(f: impl FnOnce(&mut FrameBufferWriter) -> R) -> Option<R> {
+    let writer = WRITER.get()?;
+    Some(interrupts::without_interrupts(|| f(&mut writer.lock())))
+}
+
+/// Like [with_writer], but for virtual console `index`.
+pub fn with_console<R>(index: usize, f: impl FnOnce(&mut FrameBufferWriter) -> R) -> Option<R> {
+    with_writer(|writer| writer.with_console(index, f))
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::writer::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Like [print!], but meant for error paths (exception and panic handlers) that may run while
+/// the console lock is held by the code they interrupted.
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => ($crate::writer::_eprint(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! eprintln {
+    () => ($crate::eprint!("\n"));
+    ($($arg:tt)*) => ($crate::eprint!("{}\n", format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    with_writer(|writer| writer.write_fmt(args).unwrap());
+}
+
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    with_writer_forced(|writer| {
+        let _ = writer.write_fmt(args);
+        // the caller may never return to a point where the screen is flushed
+        writer.flush();
+    });
+}
+
+/// Like [with_writer], but for error paths: takes the console even if its lock is held and
+/// writes to the console on the screen, where errors are seen.
+pub fn with_writer_forced<R>(f: impl FnOnce(&mut FrameBufferWriter) -> R) -> Option<R> {
+    let writer = WRITER.get()?;
+    Some(interrupts::without_interrupts(|| {
+        // With interrupts off, a held lock can only belong to code we interrupted on this
+        // core, which will never get to run again before we return. Take the console anyway.
+        if writer.is_locked() {
+            unsafe { writer.force_unlock() };
+        }
+        let mut writer = writer.lock();
+        let active = writer.active_console();
+        writer.with_console(active, f)
+    }))
+}
+
+/// Gives the console a back buffer on the heap, so that drawing does not touch slow video
+/// memory directly. Returns false if the heap has no room for it or the console is not
+/// initialised.
+pub fn enable_double_buffering() -> bool {
+    static ENABLED: Once<bool> = Once::new();
+    *ENABLED.call_once(|| {
+        with_writer(|writer| {
+            let mut buffer = Vec::new();
+            if buffer.try_reserve_exact(writer.info.byte_len).is_err() {
+                return false;
+            }
+            buffer.resize(writer.info.byte_len, 0);
+            // the console keeps the buffer for good
+            writer.enable_back_buffer(buffer.leak()).is_ok()
+        }) == Some(true)
+    })
+}
+
+/// How long the caret stays shown, and then hidden, while it blinks.
+const CARET_BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Blinks the caret of the console on the screen, forever. Runs as a task; the blink shows up
+/// with the next flush.
+pub async fn blink_caret() {
+    loop {
+        time::sleep(CARET_BLINK_INTERVAL).await;
+        with_writer(|writer| writer.blink_caret());
+    }
+}
+
+/// Carries out a requested console switch and flushes the back buffer.
+fn on_tick() {
+    // whoever holds the lock flushes soon enough themselves; a switch waits for the next tick
+    let Some(mut writer) = WRITER.get().and_then(|writer| writer.try_lock()) else { return };
+    let requested = REQUESTED_CONSOLE.swap(CONSOLES, Ordering::Relaxed);
+    if requested < CONSOLES {
+        writer.switch_to(requested);
+    }
+    writer.flush();
+}
+
+/// Converts a pixel position of the text grid for drawing on a [Canvas].
+fn cell_corner(x: usize, y: usize) -> Point {
+    Point::new(x as isize, y as isize)
+}
+
+/// A glyph in the colors of the text it is part of, for drawing with [Canvas::blit].
+struct TextGlyph {
+    glyph: Glyph,
+    foreground: Color,
+    background: Color,
+}
+
+impl Bitmap for TextGlyph {
+    fn width(&self) -> usize {
+        self.glyph.width()
+    }
+
+    fn height(&self) -> usize {
+        self.glyph.height()
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        // the glyph intensity is how much of the text color covers the background
+        Some(self.background.blend(self.foreground, self.glyph.intensity(x, y)))
+    }
+}
+
+/// Reads the color that follows SGR 38 or 48: `5;n` for the 256-color palette or `2;r;g;b`.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut channel = || params.next().map(|value| value.min(255) as u8);
+    match channel()? {
+        5 => Some(Color::from_ansi_256(channel()?)),
+        2 => Some(Color::new(channel()?, channel()?, channel()?)),
+        _ => None,
+    }
+}
@@ -0,0 +1,153 @@
+/// Most parameters kept for one control sequence; further ones are ignored.
+const MAX_PARAMS: usize = 16;
+
+const ESC: char = '\u{1b}';
+/// CAN and SUB abort an escape sequence in progress.
+const CAN: char = '\u{18}';
+const SUB: char = '\u{1a}';
+
+/// What the writer should do in response to the characters fed to [Parser::advance].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Render the character, or handle it as a plain control character like `\n`.
+    Print(char),
+    /// `ESC 7`: remember the cursor position and text attributes.
+    SaveCursor,
+    /// `ESC 8`: go back to what [Action::SaveCursor] remembered.
+    RestoreCursor,
+    /// A complete `ESC [` control sequence.
+    Csi(Csi),
+}
+
+/// A control sequence introducer (`ESC [`) sequence such as `ESC [ 1 ; 31 m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    /// Set for sequences with a private marker like the `?` in `ESC [ ? 25 l`.
+    pub private: bool,
+    /// The character that ends the sequence and selects the function.
+    pub function: char,
+}
+
+impl Csi {
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count.min(MAX_PARAMS)]
+    }
+
+    /// Returns parameter `index`, or `default` if it is missing or 0 as for cursor movements.
+    pub fn param_or(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&param) if param != 0 => param,
+            _ => default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Ground,
+    Escape,
+    Csi(Csi),
+    /// Skipping a sequence with intermediate bytes, none of which are supported.
+    IgnoreCsi,
+}
+
+/// A VT100-style escape sequence parser fed one character at a time.
+pub struct Parser {
+    state: State,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+        }
+    }
+
+    /// Feeds `c` to the parser; returns what to do once a character or sequence is complete.
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        if c == CAN || c == SUB {
+            self.state = State::Ground;
+            return None;
+        }
+        match self.state {
+            State::Ground if c == ESC => {
+                self.state = State::Escape;
+                None
+            }
+            State::Ground => Some(Action::Print(c)),
+            State::Escape => {
+                self.state = State::Ground;
+                match c {
+                    '[' => {
+                        self.state = State::Csi(Csi {
+                            params: [0; MAX_PARAMS],
+                            count: 0,
+                            private: false,
+                            function: '\0',
+                        });
+                        None
+                    }
+                    '7' => Some(Action::SaveCursor),
+                    '8' => Some(Action::RestoreCursor),
+                    ESC => {
+                        self.state = State::Escape;
+                        None
+                    }
+                    // other escape sequences are not supported and swallowed
+                    _ => None,
+                }
+            }
+            State::Csi(mut csi) => match c {
+                '0'..='9' => {
+                    if csi.count == 0 {
+                        csi.count = 1;
+                    }
+                    if let Some(param) = csi.params.get_mut(csi.count - 1) {
+                        *param = param.saturating_mul(10).saturating_add(c as u16 - '0' as u16);
+                    }
+                    self.state = State::Csi(csi);
+                    None
+                }
+                // colons separate the parts of a color in `38:2:r:g:b`, treat them as semicolons
+                ';' | ':' => {
+                    // an empty first parameter still counts as one
+                    csi.count = csi.count.max(1) + 1;
+                    self.state = State::Csi(csi);
+                    None
+                }
+                '<' | '=' | '>' | '?' => {
+                    csi.private = true;
+                    self.state = State::Csi(csi);
+                    None
+                }
+                '\u{20}'..='\u{2f}' => {
+                    self.state = State::IgnoreCsi;
+                    None
+                }
+                '\u{40}'..='\u{7e}' => {
+                    self.state = State::Ground;
+                    csi.function = c;
+                    Some(Action::Csi(csi))
+                }
+                ESC => {
+                    self.state = State::Escape;
+                    None
+                }
+                // control characters are carried out in the middle of a sequence
+                c if c.is_control() => Some(Action::Print(c)),
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            },
+            State::IgnoreCsi => {
+                if ('\u{40}'..='\u{7e}').contains(&c) {
+                    self.state = State::Ground;
+                }
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+mod psf;
+
+pub use psf::{PsfError, PsfFont};
+
+use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar};
+use spin::Once;
+
+use super::constants::font_constants::{BACKUP_CHAR, CHAR_RASTER_HEIGHT, FONT_WEIGHT};
+
+/// What the console draws text with.
+#[derive(Clone, Copy)]
+pub enum Font {
+    /// The antialiased glyphs built in from [noto_sans_mono_bitmap].
+    Noto { size: RasterHeight, weight: FontWeight },
+    /// A bitmap font loaded at runtime with [load_psf].
+    Psf(&'static PsfFont),
+}
+
+impl Font {
+    pub const DEFAULT: Font = Font::Noto {
+        size: CHAR_RASTER_HEIGHT,
+        weight: FONT_WEIGHT,
+    };
+
+    /// Width of every glyph in pixels.
+    pub fn width(&self) -> usize {
+        match *self {
+            Font::Noto { size, weight } => get_raster_width(weight, size),
+            Font::Psf(font) => font.width(),
+        }
+    }
+
+    /// Height of every glyph in pixels.
+    pub fn height(&self) -> usize {
+        match *self {
+            Font::Noto { size, .. } => size.val(),
+            Font::Psf(font) => font.height(),
+        }
+    }
+
+    /// Returns the glyph for `c` or for [BACKUP_CHAR] if the font lacks it. Bold glyphs are
+    /// taken from the bold weight or, in bitmap fonts, thickened by a pixel.
+    pub fn glyph(&self, c: char, bold: bool) -> Glyph {
+        match *self {
+            Font::Noto { size, weight } => {
+                let weight = if bold { FontWeight::Bold } else { weight };
+                let get = |c: char| get_raster(c, weight, size);
+                let raster = get(c)
+                    .or_else(|| get(BACKUP_CHAR))
+                    .expect("Should get raster of backup char.");
+                Glyph::Noto(raster)
+            }
+            Font::Psf(font) => {
+                let index = font
+                    .glyph_index(c)
+                    .or_else(|| font.glyph_index(BACKUP_CHAR))
+                    .or_else(|| font.glyph_index('?'))
+                    .unwrap_or(0);
+                Glyph::Psf { font, index, bold }
+            }
+        }
+    }
+}
+
+/// The image of one character.
+pub enum Glyph {
+    Noto(RasterizedChar),
+    Psf {
+        font: &'static PsfFont,
+        index: usize,
+        bold: bool,
+    },
+}
+
+impl Glyph {
+    pub fn width(&self) -> usize {
+        match self {
+            Glyph::Noto(raster) => raster.width(),
+            Glyph::Psf { font, .. } => font.width(),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self {
+            Glyph::Noto(raster) => raster.height(),
+            Glyph::Psf { font, .. } => font.height(),
+        }
+    }
+
+    /// How much of pixel `x`, `y` the glyph covers, from 0 (background) to 255 (text color).
+    pub fn intensity(&self, x: usize, y: usize) -> u8 {
+        match self {
+            Glyph::Noto(raster) => raster.raster()[y][x],
+            Glyph::Psf { font, index, bold } => {
+                // bold bitmap glyphs are smeared one pixel to the right, like on a VGA console
+                let smeared = *bold && x > 0 && font.is_set(*index, x - 1, y);
+                if font.is_set(*index, x, y) || smeared {
+                    u8::MAX
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+static PSF_FONT: Once<PsfFont> = Once::new();
+
+/// Parses the PSF font in `data` and keeps it for the rest of the kernel's lifetime. Only one
+/// font can be loaded; later calls return the first one.
+pub fn load_psf(data: &'static [u8]) -> Result<&'static PsfFont, PsfError> {
+    if let Some(font) = PSF_FONT.get() {
+        return Ok(font);
+    }
+    let font = PsfFont::parse(data)?;
+    Ok(PSF_FONT.call_once(|| font))
+}
+
+/// The font loaded by [load_psf], if any.
+pub fn loaded_psf() -> Option<&'static PsfFont> {
+    PSF_FONT.get()
+}
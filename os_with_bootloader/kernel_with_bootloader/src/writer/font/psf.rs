@@ -0,0 +1,239 @@
+//! PC Screen Font files, version 1 (as used by the Linux console) and 2.
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+/// The font has 512 glyphs instead of 256.
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+const PSF1_MODE_SEQUENCES: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_START_SEQUENCE: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+const PSF2_FLAG_HAS_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_START_SEQUENCE: u8 = 0xfe;
+
+/// Marks a code point below 256 that no glyph is mapped to.
+const NO_GLYPH: u16 = u16::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsfError {
+    /// Neither the PSF1 nor the PSF2 magic number.
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The header promises more glyph data than the file contains.
+    Truncated,
+    /// The glyphs are empty, or too large to be a console font.
+    BadGlyphSize,
+}
+
+/// Which code points the glyphs show, in the encoding of the file version.
+#[derive(Clone, Copy)]
+enum UnicodeTable {
+    /// Little-endian UCS-2 values.
+    Psf1(&'static [u8]),
+    /// UTF-8 sequences.
+    Psf2(&'static [u8]),
+}
+
+/// A bitmap font parsed from a PSF1 or PSF2 file. The glyph data is used in place.
+pub struct PsfFont {
+    glyphs: &'static [u8],
+    count: usize,
+    width: usize,
+    height: usize,
+    bytes_per_glyph: usize,
+    unicode: Option<UnicodeTable>,
+    /// Glyph index of each code point below 256, so that most text needs no table search.
+    latin1: [u16; 256],
+}
+
+impl PsfFont {
+    /// Glyphs wider or taller than this are rejected; no console font comes close.
+    const MAX_GLYPH_SIZE: usize = 64;
+
+    pub fn parse(data: &'static [u8]) -> Result<Self, PsfError> {
+        let mut font = if data.starts_with(&PSF1_MAGIC) {
+            Self::parse_psf1(data)?
+        } else if data.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf2(data)?
+        } else {
+            return Err(PsfError::BadMagic);
+        };
+        let mut latin1 = [NO_GLYPH; 256];
+        for (index, c) in font.mappings() {
+            if let Some(slot) = latin1.get_mut(c as usize) {
+                if *slot == NO_GLYPH {
+                    *slot = index as u16;
+                }
+            }
+        }
+        font.latin1 = latin1;
+        Ok(font)
+    }
+
+    fn parse_psf1(data: &'static [u8]) -> Result<Self, PsfError> {
+        let header = data.get(..PSF1_HEADER_SIZE).ok_or(PsfError::Truncated)?;
+        let (mode, height) = (header[2], header[3] as usize);
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let has_table = mode & (PSF1_MODE_HAS_TABLE | PSF1_MODE_SEQUENCES) != 0;
+        let table = has_table.then_some(UnicodeTable::Psf1 as fn(_) -> _);
+        // PSF1 glyphs are always 8 pixels wide, one byte per row
+        Self::new(data, PSF1_HEADER_SIZE, count, 8, height, height, table)
+    }
+
+    fn parse_psf2(data: &'static [u8]) -> Result<Self, PsfError> {
+        let header = data.get(..PSF2_HEADER_SIZE).ok_or(PsfError::Truncated)?;
+        let field = |index: usize| {
+            let bytes = &header[index * 4..index * 4 + 4];
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        };
+        let version = field(1);
+        if version != 0 {
+            return Err(PsfError::UnsupportedVersion(version));
+        }
+        let [header_size, count, bytes_per_glyph, height, width] =
+            [field(2), field(4), field(5), field(6), field(7)].map(|value| value as usize);
+        let has_table = field(3) & PSF2_FLAG_HAS_TABLE != 0;
+        let table = has_table.then_some(UnicodeTable::Psf2 as fn(_) -> _);
+        Self::new(data, header_size, count, width, height, bytes_per_glyph, table)
+    }
+
+    /// Checks the glyph geometry against the file size and splits off the glyph data.
+    fn new(
+        data: &'static [u8],
+        offset: usize,
+        count: usize,
+        width: usize,
+        height: usize,
+        bytes_per_glyph: usize,
+        table: Option<fn(&'static [u8]) -> UnicodeTable>,
+    ) -> Result<Self, PsfError> {
+        if width == 0
+            || height == 0
+            || width > Self::MAX_GLYPH_SIZE
+            || height > Self::MAX_GLYPH_SIZE
+            || bytes_per_glyph < width.div_ceil(8) * height
+        {
+            return Err(PsfError::BadGlyphSize);
+        }
+        let end = count
+            .checked_mul(bytes_per_glyph)
+            .and_then(|size| size.checked_add(offset))
+            .filter(|end| *end <= data.len())
+            .ok_or(PsfError::Truncated)?;
+        Ok(Self {
+            glyphs: &data[offset..end],
+            count,
+            width,
+            height,
+            bytes_per_glyph,
+            unicode: table.map(|table| table(&data[end..])),
+            latin1: [NO_GLYPH; 256],
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Index of the glyph showing `c`. Without a unicode table, glyphs are indexed by code point.
+    pub fn glyph_index(&self, c: char) -> Option<usize> {
+        if let Some(&index) = self.latin1.get(c as usize) {
+            return (index != NO_GLYPH).then_some(index as usize);
+        }
+        self.mappings().find(|&(_, mapped)| mapped == c).map(|(index, _)| index)
+    }
+
+    /// Whether pixel `x`, `y` of glyph `index` is set.
+    pub fn is_set(&self, index: usize, x: usize, y: usize) -> bool {
+        let row = index * self.bytes_per_glyph + y * self.width.div_ceil(8);
+        self.glyphs[row + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Every glyph index with the single code points it shows, in file order.
+    fn mappings(&self) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut index = 0;
+        let mut in_sequence = false;
+        let mut position = 0;
+        let identity = self.unicode.is_none().then_some(0..self.count);
+        let table = self.unicode.into_iter().flat_map(move |table| {
+            core::iter::from_fn(move || loop {
+                let entry = table.next_entry(&mut position)?;
+                match entry {
+                    Entry::Separator => {
+                        index += 1;
+                        in_sequence = false;
+                    }
+                    Entry::StartSequence => in_sequence = true,
+                    // a sequence of combining characters, which the console does not compose
+                    Entry::Char(_) if in_sequence => {}
+                    Entry::Char(c) => return Some((index, c)),
+                    Entry::Invalid => {}
+                }
+            })
+        });
+        identity
+            .into_iter()
+            .flatten()
+            .filter_map(|index| char::from_u32(index as u32).map(|c| (index, c)))
+            .chain(table)
+            .filter(move |&(index, _)| index < self.count)
+    }
+}
+
+enum Entry {
+    Char(char),
+    Separator,
+    StartSequence,
+    /// Code units that do not form a character; skipped.
+    Invalid,
+}
+
+impl UnicodeTable {
+    /// Decodes the entry at `position` and moves past it.
+    fn next_entry(&self, position: &mut usize) -> Option<Entry> {
+        match *self {
+            UnicodeTable::Psf1(table) => {
+                let bytes = table.get(*position..*position + 2)?;
+                *position += 2;
+                Some(match u16::from_le_bytes([bytes[0], bytes[1]]) {
+                    PSF1_SEPARATOR => Entry::Separator,
+                    PSF1_START_SEQUENCE => Entry::StartSequence,
+                    value => char::from_u32(value as u32).map_or(Entry::Invalid, Entry::Char),
+                })
+            }
+            UnicodeTable::Psf2(table) => {
+                let first = *table.get(*position)?;
+                let length = match first {
+                    PSF2_SEPARATOR => {
+                        *position += 1;
+                        return Some(Entry::Separator);
+                    }
+                    PSF2_START_SEQUENCE => {
+                        *position += 1;
+                        return Some(Entry::StartSequence);
+                    }
+                    0x00..=0x7f => 1,
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => {
+                        *position += 1;
+                        return Some(Entry::Invalid);
+                    }
+                };
+                let bytes = table.get(*position..*position + length)?;
+                *position += length;
+                let c = core::str::from_utf8(bytes).ok().and_then(|s| s.chars().next());
+                Some(c.map_or(Entry::Invalid, Entry::Char))
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use core::ops::Range;
+
+use super::Color;
+
+/// Widest line the scrollback records; characters further right are shown but not kept.
+pub const MAX_COLUMNS: usize = 256;
+
+/// Number of text lines stored, including the ones currently on screen.
+pub const CAPACITY: usize = 256;
+
+/// One character of recorded text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub foreground: Color,
+    pub background: Color,
+    pub bold: bool,
+}
+
+impl Cell {
+    /// A cell nothing was written to. All zero, so a [Scrollback] can live in `.bss`.
+    pub const EMPTY: Cell = Cell {
+        c: '\0',
+        foreground: Color::BLACK,
+        background: Color::BLACK,
+        bold: false,
+    };
+}
+
+/// The text shown on screen plus the lines that scrolled off its top, so that they can be
+/// paged back to. Lines are numbered from the first one ever written and stored in a ring.
+pub struct Scrollback {
+    lines: [[Cell; MAX_COLUMNS]; CAPACITY],
+    /// Number of the oldest line still stored.
+    oldest: usize,
+    /// Number of the line in the top row of the screen.
+    top: usize,
+    /// How many lines above the screen are kept at most.
+    limit: usize,
+}
+
+impl Scrollback {
+    pub const fn new() -> Self {
+        Self {
+            lines: [[Cell::EMPTY; MAX_COLUMNS]; CAPACITY],
+            oldest: 0,
+            top: 0,
+            limit: CAPACITY,
+        }
+    }
+
+    /// Returns screen row `row` as it was `offset` lines back in the history.
+    pub fn row(&self, row: usize, offset: usize) -> &[Cell; MAX_COLUMNS] {
+        &self.lines[(self.top - offset + row) % CAPACITY]
+    }
+
+    /// Records `cell` at `row`, `column` of the screen.
+    pub fn set(&mut self, row: usize, column: usize, cell: Cell) {
+        if column < MAX_COLUMNS {
+            self.lines[(self.top + row) % CAPACITY][column] = cell;
+        }
+    }
+
+    /// Forgets the text in `columns` of screen row `row`.
+    pub fn erase(&mut self, row: usize, columns: Range<usize>) {
+        let columns = columns.start.min(MAX_COLUMNS)..columns.end.min(MAX_COLUMNS);
+        self.lines[(self.top + row) % CAPACITY][columns].fill(Cell::EMPTY);
+    }
+
+    /// Number of lines above the screen that can be paged back to.
+    pub fn history_len(&self) -> usize {
+        self.top - self.oldest
+    }
+
+    /// Sets how many lines above a screen of `rows` rows are kept, within what fits.
+    /// Returns the limit that took effect.
+    pub fn set_limit(&mut self, limit: usize, rows: usize) -> usize {
+        self.limit = limit.min(CAPACITY.saturating_sub(rows));
+        self.forget_excess();
+        self.limit
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Moves `count` lines of a screen with `rows` rows into the history and clears the rows
+    /// that come into view at the bottom.
+    pub fn scroll(&mut self, count: usize, rows: usize) {
+        self.limit = self.limit.min(CAPACITY.saturating_sub(rows));
+        for _ in 0..count {
+            self.top += 1;
+            self.forget_excess();
+            self.lines[(self.top + rows - 1) % CAPACITY] = [Cell::EMPTY; MAX_COLUMNS];
+        }
+    }
+
+    /// Moves the rows of a screen with `rows` rows that have text on them into the history and
+    /// blanks the screen.
+    pub fn clear_screen(&mut self, rows: usize) {
+        let used = (0..rows)
+            .rposition(|row| self.row(row, 0).iter().any(|cell| *cell != Cell::EMPTY))
+            .map_or(0, |last| last + 1);
+        self.scroll(used, rows);
+        for row in 0..rows {
+            self.lines[(self.top + row) % CAPACITY] = [Cell::EMPTY; MAX_COLUMNS];
+        }
+    }
+
+    fn forget_excess(&mut self) {
+        self.oldest = self.oldest.max(self.top.saturating_sub(self.limit));
+    }
+}
@@ -11,6 +11,8 @@ fn main() {
     let uefi = true;
 
     let mut cmd = std::process::Command::new("qemu-system-x86_64");
+    // forward COM1 to our terminal so kernel output can be captured headless
+    cmd.arg("-serial").arg("stdio");
     if uefi {
         cmd.arg("-bios").arg(ovmf_prebuilt::ovmf_pure_efi());
         cmd.arg("-drive").arg(format!("format=raw,file={uefi_path}"));